@@ -0,0 +1,303 @@
+use sdl2::{keyboard::Keycode, rect::{Point, Rect}};
+
+use crate::{Cross, Direction, Itineraire, Settings};
+
+/// The four sides of the intersection, named after where the vehicles come from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Approach {
+    North,
+    East,
+    South,
+    West,
+}
+
+/// Index in the key points, counted from the start or from the end of the list.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum KeyPoint {
+    First(usize),
+    Last(usize),
+}
+
+impl KeyPoint {
+    pub fn resolve(&self, key_points: &[i32]) -> i32 {
+        match self {
+            KeyPoint::First(i) => key_points[*i],
+            KeyPoint::Last(i) => key_points[key_points.len() - 1 - i],
+        }
+    }
+}
+
+/// Everything that depends on the side a vehicle enters from.
+#[derive(Debug, Clone)]
+pub struct ApproachSpec {
+    pub approach: Approach,
+    pub cross: Cross,
+    /// Where the vehicles head to while on the approach.
+    pub direction: Direction,
+    /// Unit vector of `direction` in screen coordinates.
+    pub travel: (i32, i32),
+    /// Angle the sprites are drawn with while heading in `direction`.
+    pub angle: f64,
+    /// Arrow key spawning a vehicle on the approach.
+    pub key: Keycode,
+    /// Key point of each inbound lane, across the road.
+    pub lanes: [(Itineraire, usize); 3],
+    /// Key point the vehicles spawn at, along the road.
+    pub entry: KeyPoint,
+    /// Key point of the stop line, along the road.
+    pub stop: KeyPoint,
+}
+
+/// The single table the spawn positions, headings, stop lines and keyboard
+/// bindings are read from.
+pub const APPROACHES: [ApproachSpec; 4] = [
+    ApproachSpec {
+        approach: Approach::North,
+        cross: Cross::First,
+        direction: Direction::Down,
+        travel: (0, 1),
+        angle: -90.0,
+        key: Keycode::Down,
+        lanes: [(Itineraire::Right, 3), (Itineraire::Straight, 5), (Itineraire::Left, 7)],
+        entry: KeyPoint::First(0),
+        stop: KeyPoint::First(2),
+    },
+    ApproachSpec {
+        approach: Approach::East,
+        cross: Cross::Third,
+        direction: Direction::Left,
+        travel: (-1, 0),
+        angle: 360.0,
+        key: Keycode::Left,
+        lanes: [(Itineraire::Right, 3), (Itineraire::Straight, 5), (Itineraire::Left, 7)],
+        entry: KeyPoint::Last(0),
+        stop: KeyPoint::Last(1),
+    },
+    ApproachSpec {
+        approach: Approach::South,
+        cross: Cross::Fourth,
+        direction: Direction::Up,
+        travel: (0, -1),
+        angle: 90.0,
+        key: Keycode::Up,
+        lanes: [(Itineraire::Left, 9), (Itineraire::Straight, 11), (Itineraire::Right, 13)],
+        entry: KeyPoint::Last(0),
+        stop: KeyPoint::Last(1),
+    },
+    ApproachSpec {
+        approach: Approach::West,
+        cross: Cross::Second,
+        direction: Direction::Right,
+        travel: (1, 0),
+        angle: 180.0,
+        key: Keycode::Right,
+        lanes: [(Itineraire::Left, 9), (Itineraire::Straight, 11), (Itineraire::Right, 13)],
+        entry: KeyPoint::First(0),
+        stop: KeyPoint::First(2),
+    },
+];
+
+impl Approach {
+    pub const ALL: [Approach; 4] = [Approach::North, Approach::East, Approach::South, Approach::West];
+
+    pub fn spec(&self) -> &'static ApproachSpec {
+        APPROACHES.iter().find(|s| s.approach == *self).unwrap()
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Approach::North => "North",
+            Approach::East => "East",
+            Approach::South => "South",
+            Approach::West => "West",
+        }
+    }
+
+    pub fn cross(&self) -> Cross {
+        self.spec().cross
+    }
+
+    pub fn direction(&self) -> Direction {
+        self.spec().direction
+    }
+
+    pub fn from_direction(direction: Direction) -> Option<Approach> {
+        APPROACHES.iter().find(|s| s.direction == direction).map(|s| s.approach)
+    }
+
+    pub fn from_key(key: Keycode) -> Option<Approach> {
+        APPROACHES.iter().find(|s| s.key == key).map(|s| s.approach)
+    }
+
+    fn from_travel(travel: (i32, i32)) -> Approach {
+        APPROACHES.iter().find(|s| s.travel == travel).map(|s| s.approach).unwrap()
+    }
+
+    /// The approach on the other side of the intersection.
+    pub fn opposite(&self) -> Approach {
+        let (dx, dy) = self.spec().travel;
+        Approach::from_travel((-dx, -dy))
+    }
+
+    /// The approach whose vehicles head the same way as the ones leaving with `itineraire`.
+    pub fn exit(&self, itineraire: Itineraire) -> Approach {
+        let (dx, dy) = self.spec().travel;
+        match itineraire {
+            Itineraire::Straight => *self,
+            Itineraire::UTurn => self.opposite(),
+            // the y axis points down the screen.
+            Itineraire::Right => Approach::from_travel((-dy, dx)),
+            Itineraire::Left => Approach::from_travel((dy, -dx)),
+        }
+    }
+
+    /// Key point, across the road, of the inbound lane used by `itineraire`,
+    /// the U-turns share the inner lane with the left turns.
+    pub fn lane(&self, itineraire: Itineraire) -> usize {
+        let itineraire = match itineraire {
+            Itineraire::UTurn => Itineraire::Left,
+            itineraire => itineraire,
+        };
+        self.spec().lanes.iter().find(|(i, _)| *i == itineraire).unwrap().1
+    }
+
+    fn is_vertical(&self) -> bool {
+        self.spec().travel.0 == 0
+    }
+
+    /// Point from the lane across the road and a coordinate along it.
+    fn point(&self, across: i32, along: i32) -> Point {
+        if self.is_vertical() {
+            Point::new(across, along)
+        } else {
+            Point::new(along, across)
+        }
+    }
+
+    fn along<'a>(&self, settings: &'a Settings) -> &'a [i32] {
+        if self.is_vertical() {
+            &settings.horizontal_key_points
+        } else {
+            &settings.vertical_key_points
+        }
+    }
+
+    fn across<'a>(&self, settings: &'a Settings) -> &'a [i32] {
+        if self.is_vertical() {
+            &settings.vertical_key_points
+        } else {
+            &settings.horizontal_key_points
+        }
+    }
+
+    pub fn spawn(&self, itineraire: Itineraire, settings: &Settings) -> Point {
+        let across = self.across(settings)[self.lane(itineraire)];
+        self.point(across, self.spec().entry.resolve(self.along(settings)))
+    }
+
+    /// The stop line, the coordinate across the road is left to 0.
+    pub fn stop_point(&self, settings: &Settings) -> Point {
+        self.point(0, self.spec().stop.resolve(self.along(settings)))
+    }
+
+    /// Where a vehicle following `itineraire` changes direction, it joins the
+    /// lane the vehicles of the exit approach use for the same itineraire.
+    pub fn turn_point(&self, itineraire: Itineraire, settings: &Settings) -> Option<Point> {
+        if itineraire == Itineraire::Straight {
+            return None;
+        }
+
+        let across = self.across(settings)[self.lane(itineraire)];
+        if itineraire == Itineraire::UTurn {
+            return Some(self.point(across, self.u_turn_along(settings)));
+        }
+
+        let exit = self.exit(itineraire);
+        let along = exit.across(settings)[exit.lane(itineraire)];
+        Some(self.point(across, along))
+    }
+
+    /// Where a U-turn heads back: on the first lane across the intersection,
+    /// the same as the first turn, and on the lane the left turns leave by.
+    pub fn turn_back_point(&self, itineraire: Itineraire, settings: &Settings) -> Option<Point> {
+        if itineraire != Itineraire::UTurn {
+            return None;
+        }
+
+        let back = self.opposite();
+        let across = self.across(settings)[back.lane(Itineraire::Left)];
+        Some(self.point(across, self.u_turn_along(settings)))
+    }
+
+    /// Coordinate, along the road, of the lane right past the stop line the U-turns cross on.
+    fn u_turn_along(&self, settings: &Settings) -> i32 {
+        let along = self.along(settings);
+        let stop = match self.spec().stop {
+            KeyPoint::First(i) => i,
+            KeyPoint::Last(i) => along.len() - 1 - i,
+        };
+        // the positions are the top left corner of the vehicles.
+        if self.spec().travel.0 + self.spec().travel.1 > 0 {
+            along[stop + 1]
+        } else {
+            along[stop - 2]
+        }
+    }
+
+    /// The leg the vehicles following `itineraire` leave the intersection by.
+    pub fn exit_leg(&self, itineraire: Itineraire) -> Approach {
+        self.exit(itineraire).opposite()
+    }
+
+    /// The crosswalk over the whole road of the approach, right behind a
+    /// vehicle held on the stop line.
+    pub fn crosswalk(&self, settings: &Settings) -> Rect {
+        let across = self.across(settings);
+        let (from, to) = (across[2], across[across.len() - 3]);
+        let stop = self.spec().stop.resolve(self.along(settings));
+        let width = settings.vehicle;
+        let start = if self.spec().travel.0 + self.spec().travel.1 > 0 {
+            stop - width
+        } else {
+            stop + settings.vehicle
+        };
+
+        if self.is_vertical() {
+            Rect::new(from, start, (to - from) as u32, width as u32)
+        } else {
+            Rect::new(start, from, width as u32, (to - from) as u32)
+        }
+    }
+
+    /// Spawn point, turn points if any and the point the vehicles leave the map at.
+    pub fn path(&self, itineraire: Itineraire, settings: &Settings) -> Vec<Point> {
+        let start = self.spawn(itineraire, settings);
+        let exit = self.exit(itineraire);
+        let leave = exit.opposite().spec().entry.resolve(exit.along(settings));
+
+        if let (Some(turn), Some(back)) = (self.turn_point(itineraire, settings), self.turn_back_point(itineraire, settings)) {
+            let end = if exit.is_vertical() { Point::new(back.x, leave) } else { Point::new(leave, back.y) };
+            return vec![start, turn, back, end];
+        }
+
+        match self.turn_point(itineraire, settings) {
+            Some(turn) => {
+                let end = if exit.is_vertical() { Point::new(turn.x, leave) } else { Point::new(leave, turn.y) };
+                vec![start, turn, end]
+            }
+            None => vec![start, self.point(self.across(settings)[self.lane(itineraire)], leave)],
+        }
+    }
+}
+
+impl From<Cross> for Approach {
+    fn from(cross: Cross) -> Approach {
+        APPROACHES.iter().find(|s| s.cross == cross).map(|s| s.approach).unwrap()
+    }
+}
+
+impl From<Approach> for Cross {
+    fn from(approach: Approach) -> Cross {
+        approach.cross()
+    }
+}
@@ -0,0 +1,83 @@
+use std::fs;
+
+use smart_road::*;
+
+pub fn main() {
+    // runs every controller of `--controllers smart-intersection,...` (all of them by
+    // default) under every demand of `--demand 10,20,40`, in vehicles per minute on
+    // each approach, `--seeds <n>` times for `--duration <seconds>` each, and writes
+    // the table to `--out <file>` or the standard output, the options of
+    // `Settings::apply_args` set up the simulation. With `--capacity` it ramps
+    // instead the demand of each movement by `--step <veh/min>`, the other movements
+    // at the first demand (1 by default), and writes the capacity table of each
+    // controller, with the v/c of the `--volumes north-left=3,...` the movements
+    // carry (the first demand for the ones left out).
+    let args: Vec<String> = std::env::args().collect();
+    let option = |name: &str| args.iter().position(|a| a == name).and_then(|i| args.get(i + 1)).cloned();
+    let list = |name: &str, default: &str| -> Vec<String> {
+        option(name).unwrap_or(default.to_string()).split(',').map(|s| s.trim().to_string()).collect()
+    };
+
+    let mut settings = Settings::new(1000, 1000, 30, 1, 100.0);
+    settings.apply_args(&args).unwrap_or_else(|e| panic!("cannot use {}", e));
+    let settings = Rc::new(settings);
+
+    let all: Vec<String> = Controller::ALL.iter().map(|c| c.name().replace(' ', "-")).collect();
+    let controllers: Vec<Controller> = list("--controllers", &all.join(","))
+        .iter()
+        .map(|name| Controller::from_name(name).unwrap_or_else(|| panic!("unknown controller {}, use one of {}", name, all.join(", "))))
+        .collect();
+    let demands: Vec<f64> = list("--demand", "10,20,40")
+        .iter()
+        .map(|d| d.parse().unwrap_or_else(|_| panic!("cannot use the demand {}", d)))
+        .collect();
+    let seeds: usize = option("--seeds").map_or(5, |n| n.parse().unwrap_or_else(|_| panic!("cannot use {} seeds", n)));
+    let duration: f64 = option("--duration").map_or(300.0, |d| d.parse().unwrap_or_else(|_| panic!("cannot use the duration {}", d)));
+
+    if args.iter().any(|a| a == "--capacity") {
+        let step: f64 = option("--step").map_or(5.0, |s| s.parse().unwrap_or_else(|_| panic!("cannot use the step {}", s)));
+        let background = if option("--demand").is_some() { demands[0] } else { 1.0 };
+        let volumes = option("--volumes").map_or(Vec::new(), |text| {
+            CapacityAnalysis::parse_volumes(&text).unwrap_or_else(|e| panic!("cannot use the volumes {}", e))
+        });
+        let mut tables = String::new();
+        for controller in controllers.iter() {
+            eprintln!("capacity of {} over {} veh/min", controller.name(), background);
+            let analysis = CapacityAnalysis {
+                controller: *controller,
+                background,
+                volumes: volumes.clone(),
+                step,
+                max_demand: 60.0,
+                duration,
+                seed: 0,
+            };
+            tables += &format!("{}\n\n{}\n", controller.name(), capacity_table(&analysis.run(&settings)));
+        }
+        match option("--out") {
+            Some(path) => fs::write(&path, tables).unwrap_or_else(|e| panic!("cannot write {}: {}", path, e)),
+            None => print!("{}", tables),
+        }
+        return;
+    }
+
+    let mut results = Vec::new();
+    for controller in controllers.iter() {
+        for demand in demands.iter() {
+            let experiment = Experiment { controller: *controller, demand: *demand, duration };
+            let runs: Vec<Run> = (0..seeds)
+                .map(|seed| {
+                    eprintln!("{} at {} veh/min, seed {}", controller.name(), demand, seed);
+                    experiment.run(&settings, seed)
+                })
+                .collect();
+            results.push((experiment, runs));
+        }
+    }
+
+    let table = summary_table(&results);
+    match option("--out") {
+        Some(path) => fs::write(&path, table).unwrap_or_else(|e| panic!("cannot write {}: {}", path, e)),
+        None => print!("{}", table),
+    }
+}
@@ -0,0 +1,30 @@
+use std::io::{self, BufRead, Write};
+
+use smart_road::*;
+
+pub fn main() {
+    // serves the simulation as a reinforcement learning environment: one JSON
+    // request a line on the standard input, one JSON answer a line on the standard
+    // output, see `Environment::respond`. The vehicles arrive `--demand <veh/min>`
+    // on each approach, an episode lasts `--duration <seconds>` and the agent
+    // decides every `--interval <seconds>`, the options of `Settings::apply_args`
+    // set up the simulation.
+    let args: Vec<String> = std::env::args().collect();
+    let option = |name: &str| args.iter().position(|a| a == name).and_then(|i| args.get(i + 1)).cloned();
+
+    let mut settings = Settings::new(1000, 1000, 30, 1, 100.0);
+    settings.apply_args(&args).unwrap_or_else(|e| panic!("cannot use {}", e));
+    let demand: f64 = option("--demand").map_or(10.0, |d| d.parse().unwrap_or_else(|_| panic!("cannot use the demand {}", d)));
+    let duration: f64 = option("--duration").map_or(300.0, |d| d.parse().unwrap_or_else(|_| panic!("cannot use the duration {}", d)));
+    let interval: f64 = option("--interval").map_or(1.0, |i| i.parse().unwrap_or_else(|_| panic!("cannot use the interval {}", i)));
+
+    let mut environment = Environment::new(Rc::new(settings), demand, duration, interval);
+    let mut out = io::stdout().lock();
+    for line in io::stdin().lock().lines() {
+        let line = line.unwrap_or_else(|e| panic!("cannot read the standard input: {}", e));
+        if line.trim().is_empty() {
+            continue;
+        }
+        writeln!(out, "{}", environment.respond(&line)).and_then(|_| out.flush()).unwrap_or_else(|e| panic!("cannot answer: {}", e));
+    }
+}
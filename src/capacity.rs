@@ -0,0 +1,206 @@
+use std::{collections::HashSet, rc::Rc};
+
+use rand::{Rng, SeedableRng, StdRng};
+
+use crate::{lane::Stage, Approach, Controller, Cross, Itineraire, Settings, Simulation, VehicleClass, TICK};
+
+/// Share of the arrivals of a movement it has to take through the stop line,
+/// below it the queue grows for as long as the demand lasts.
+const SERVED: f64 = 0.9;
+
+const ITINERAIRES: [Itineraire; 4] = [Itineraire::Left, Itineraire::Straight, Itineraire::Right, Itineraire::UTurn];
+
+/// Vehicles per minute on some of the movements.
+pub type Volumes = Vec<((Cross, Itineraire), f64)>;
+
+/// Ramps the demand on each movement, the other ones at a background demand,
+/// until the movement no longer takes what arrives.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapacityAnalysis {
+    pub controller: Controller,
+    /// Vehicles per minute on each of the other movements.
+    pub background: f64,
+    /// Vehicles per minute each movement carries, for its volume/capacity ratio;
+    /// the movements left out carry `background`.
+    pub volumes: Volumes,
+    /// Vehicles per minute added to the movement at each step of the ramp.
+    pub step: f64,
+    /// The ramp gives up past this demand, in vehicles per minute.
+    pub max_demand: f64,
+    /// Simulated seconds per step, the warm-up of the settings included.
+    pub duration: f64,
+    pub seed: usize,
+}
+
+/// How much of a movement the controller lets through.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Capacity {
+    pub movement: (Cross, Itineraire),
+    /// Vehicles per minute through the stop line, the most seen on the ramp.
+    pub capacity: f64,
+    /// Vehicles per minute through the stop line while the route has the
+    /// intersection and a queue waiting: the discharge rate of a green.
+    pub saturation_flow: f64,
+    /// The demand the movement stopped taking at, `None` when the ramp reached
+    /// `max_demand` first and `capacity` is only a lower bound.
+    pub saturated_at: Option<f64>,
+    /// Vehicles per minute the movement carries, see `CapacityAnalysis::volumes`.
+    pub volume: f64,
+    /// The volume over the capacity.
+    pub volume_capacity: f64,
+}
+
+/// What a step of the ramp saw of the movement.
+#[derive(Default)]
+struct Step {
+    arrived: usize,
+    entered: usize,
+    entered_on_green: usize,
+    green: f64,
+    measured: f64,
+}
+
+impl CapacityAnalysis {
+    /// Every `(Cross, Itineraire)` of the layout, in the order of the lanes, see
+    /// `Lane::new`.
+    pub const MOVEMENTS: [(Cross, Itineraire); 16] = {
+        let crosses = [Cross::First, Cross::Second, Cross::Third, Cross::Fourth];
+        let mut movements = [(Cross::First, Itineraire::Left); 16];
+        let mut i = 0;
+        while i < movements.len() {
+            movements[i] = (crosses[i / ITINERAIRES.len()], ITINERAIRES[i % ITINERAIRES.len()]);
+            i += 1;
+        }
+        movements
+    };
+
+    /// Parses the volumes of movements written as `north-left=3,west-straight=5`.
+    pub fn parse_volumes(text: &str) -> Result<Volumes, String> {
+        text.split(',')
+            .map(|entry| {
+                let (movement, volume) = entry.split_once('=').ok_or_else(|| format!("`{}` is not approach-turn=volume", entry))?;
+                let (approach, turn) = movement.trim().split_once('-').ok_or_else(|| format!("`{}` is not approach-turn", movement))?;
+                let approach = Approach::ALL
+                    .into_iter()
+                    .find(|a| a.name().eq_ignore_ascii_case(approach))
+                    .ok_or_else(|| format!("unknown approach `{}`", approach))?;
+                let turn = ITINERAIRES
+                    .into_iter()
+                    .find(|i| format!("{:?}", i).eq_ignore_ascii_case(turn))
+                    .ok_or_else(|| format!("unknown turn `{}`", turn))?;
+                let volume = volume.trim().parse().map_err(|_| format!("`{}` is not a volume", volume))?;
+                Ok(((approach.cross(), turn), volume))
+            })
+            .collect()
+    }
+
+    /// Vehicles per minute `movement` carries.
+    pub fn volume(&self, movement: (Cross, Itineraire)) -> f64 {
+        self.volumes.iter().find(|(m, _)| *m == movement).map_or(self.background, |(_, volume)| *volume)
+    }
+
+    /// The capacity of every movement.
+    pub fn run(&self, settings: &Rc<Settings>) -> Vec<Capacity> {
+        Self::MOVEMENTS.into_iter().map(|movement| self.movement(settings, movement)).collect()
+    }
+
+    /// Simulates `duration` seconds at each demand of the ramp until the movement
+    /// takes less than `SERVED` of its arrivals, refused spawns included. Only
+    /// the arrivals and entries within the measurement window count.
+    pub fn movement(&self, settings: &Rc<Settings>, movement: (Cross, Itineraire)) -> Capacity {
+        let (mut capacity, mut saturation_flow, mut saturated_at) = (0.0_f64, 0.0_f64, None);
+        let mut demand = self.step;
+        while demand <= self.max_demand {
+            let step = self.simulate(settings, movement, demand);
+            if step.measured > 0.0 {
+                capacity = capacity.max(step.entered as f64 * 60.0 / step.measured);
+            }
+            if step.green > 0.0 {
+                saturation_flow = saturation_flow.max(step.entered_on_green as f64 * 60.0 / step.green);
+            }
+            if (step.entered as f64) < SERVED * step.arrived as f64 {
+                saturated_at = Some(demand);
+                break;
+            }
+            demand += self.step;
+        }
+
+        let volume = self.volume(movement);
+        Capacity {
+            movement,
+            capacity,
+            saturation_flow,
+            saturated_at,
+            volume,
+            volume_capacity: if capacity > 0.0 { volume / capacity } else { f64::INFINITY },
+        }
+    }
+
+    fn simulate(&self, settings: &Rc<Settings>, movement: (Cross, Itineraire), demand: f64) -> Step {
+        let mut simulation = Simulation::with_seed(settings.clone(), self.controller, self.seed);
+        simulation.check_invariants = false;
+        let mut rng: StdRng = SeedableRng::from_seed(&[self.seed][..]);
+
+        let mut step = Step::default();
+        let mut entered = HashSet::new();
+        for _ in 0..(self.duration / TICK) as usize {
+            let (from, to) = simulation.statistic.window;
+            let measuring = simulation.time >= from && simulation.time < to;
+            if measuring {
+                step.measured += TICK;
+            }
+            for (cross, itineraire) in Self::MOVEMENTS.iter() {
+                let rate = if (*cross, *itineraire) == movement { demand } else { self.background };
+                if rng.gen::<f64>() < rate / 60.0 * TICK {
+                    let class = VehicleClass::pick_with(&settings.mix, &mut rng);
+                    if measuring && (*cross, *itineraire) == movement {
+                        step.arrived += 1;
+                    }
+                    if let Some(lane) = simulation.lanes.iter_mut().find(|lane| lane.cross == *cross) {
+                        lane.add_vehicle(*itineraire, Some(class), &mut simulation.rng);
+                    }
+                }
+            }
+            simulation.tick();
+
+            let Some(route) = simulation.lanes
+                .iter()
+                .flat_map(|lane| lane.routes.iter())
+                .find(|r| (r.cross, r.itineraire) == movement) else { continue };
+            let green = route.stage == Stage::Crossing && route.vehicles.iter().any(|v| v.stage == Stage::Waiting);
+            if measuring && green {
+                step.green += TICK;
+            }
+            for vehicle in route.vehicles.iter().filter(|v| v.stage != Stage::Waiting) {
+                if entered.insert(vehicle.id) && measuring {
+                    step.entered += 1;
+                    if green {
+                        step.entered_on_green += 1;
+                    }
+                }
+            }
+        }
+        step
+    }
+}
+
+/// A markdown table with a row per movement, and the critical volume/capacity
+/// ratio of the intersection as the last row. The capacities of the movements
+/// are each measured alone, the ones crossing can't all reach theirs together,
+/// so they are not added up.
+pub fn capacity_table(capacities: &[Capacity]) -> String {
+    let mut table = String::from("| movement | capacity (veh/min) | saturation flow (veh/min of green) | saturated at (veh/min) | volume (veh/min) | v/c |\n");
+    table += "| -------- | ------------------ | ---------------------------------- | ---------------------- | ---------------- | --- |\n";
+    for c in capacities {
+        let saturated_at = c.saturated_at.map_or("-".to_string(), |d| format!("{:.1}", d));
+        let bound = if c.saturated_at.is_none() { "≥ " } else { "" };
+        table += &format!(
+            "| {} {:?} | {}{:.1} | {:.1} | {} | {:.1} | {:.2} |\n",
+            c.movement.0.name(), c.movement.1, bound, c.capacity, c.saturation_flow, saturated_at, c.volume, c.volume_capacity
+        );
+    }
+
+    let critical = capacities.iter().map(|c| c.volume_capacity).fold(0.0, f64::max);
+    table += &format!("| intersection, critical | | | | | {:.2} |\n", critical);
+    table
+}
@@ -1,27 +1,68 @@
-use std::{rc::Rc, time::Instant};
+use std::{rc::Rc, sync::atomic::{AtomicUsize, Ordering}};
 
 use rand::Rng;
 use sdl2::{
-    rect::{Point, Rect}, render::{Canvas, Texture, TextureQuery, WindowCanvas}, video::Window
+    rect::{Point, Rect}, render::{Canvas, Texture, WindowCanvas}, video::Window
 };
 
-use crate::{lane::Stage, Direction, Itineraire, Settings, Vilosity};
+use crate::{lane::Stage, Approach, Direction, Itineraire, Settings, VehicleClass, Vilosity, TICK};
+
+/// Draws a vehicle sprite stretched over `bounds`, the box the vehicle takes on the road.
+pub fn render_vehicle(
+    canvas: &mut WindowCanvas,
+    texture: &Texture,
+    bounds: Rect,
+    heading: f64,
+) -> Result<(), String> {
+    // the sprite is drawn lengthwise then turned around the middle of the box.
+    let length = bounds.width().max(bounds.height());
+    let width = bounds.width().min(bounds.height());
+    let screen_rect = Rect::from_center(bounds.center(), length, width);
+    canvas.copy_ex(texture, None, screen_rect, heading, None, true, true)?;
+
+    Ok(())
+}
+
+/// The box of a vehicle `length` long and `width` wide heading along `travel`,
+/// its front lined up with the front of the `vehicle` square at `position`.
+pub fn footprint(position: Point, travel: Point, vehicle: i32, length: i32, width: i32) -> Rect {
+    let along = |p: i32, d: i32| if d > 0 { p + vehicle - length } else { p };
+    let across = |p: i32| p + (vehicle - width) / 2;
+
+    if travel.x == 0 {
+        Rect::new(across(position.x), along(position.y, travel.y), width as u32, length as u32)
+    } else {
+        Rect::new(along(position.x, travel.x), across(position.y), length as u32, width as u32)
+    }
+}
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
 
 #[derive(Debug, Clone)]
 pub struct Vehicle {
+    pub id: usize,
+    pub class: VehicleClass,
+    /// Size of the vehicle in pixels, along and across its direction of travel.
+    pub length: i32,
+    pub width: i32,
     pub position: Point,
     pub route: Direction,
     pub itineraire: Itineraire,
     pub direction: f32,
     pub velocity: f32,
     pub is_changed_direction: bool,
+    /// A U-turn has made its second turn and heads back.
+    pub is_turned_back: bool,
     pub is_stopped: bool,
+    /// The speed advisory of its route slowed the vehicle down, see `Vehicle::advise`.
+    pub advised: bool,
+    /// The vehicle came down to a crawl before the stop line.
+    pub has_stopped: bool,
     pub stage: Stage,
     pub velosity_type: Vec<f32>,
 
     pub min_vilosity: f64,
     pub max_vilosity: f64,
-    prev_time: Instant,
 
 
     pub distance_traveled: f64,
@@ -31,22 +72,31 @@ pub struct Vehicle {
     accumulated_y: f32,
     angle_1: f64,
     angle_2: f64,
+    angle_3: f64,
     settings: Rc<Settings>,
 }
 
 impl Vehicle {
-    pub fn new(route: Direction, itineraire: Itineraire, settings: Rc<Settings>) -> Self {
-        let mut rng = rand::thread_rng();
-        let velosity_type = vec![0.1, 0.5, 2.0, 3.0];
-
-        let angle_1 = match route {
-            Direction::Up => 90.0,
-            Direction::Down => -90.0,
-            Direction::Left => 360.0,
-            _ => 180.0,
-        };
+    /// A vehicle of a class picked from the spawn mix of the settings.
+    pub fn new<R: Rng>(route: Direction, itineraire: Itineraire, settings: Rc<Settings>, rng: &mut R) -> Self {
+        let class = VehicleClass::pick_with(&settings.mix, rng);
+        Vehicle::with_class(route, itineraire, class, settings, rng)
+    }
+
+    /// `rng` draws the speed it arrives at and its sprite.
+    pub fn with_class<R: Rng>(route: Direction, itineraire: Itineraire, class: VehicleClass, settings: Rc<Settings>, rng: &mut R) -> Self {
+        let spec = class.spec();
+        let velosity_type = spec.speeds.to_vec();
+
+        // a vehicle without a direction is drawn as if it came from the West.
+        let approach = Approach::from_direction(route).unwrap_or(Approach::West);
+        let angle_1 = approach.spec().angle;
         
         Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            class,
+            length: (spec.length * settings.vehicle as f64).round() as i32,
+            width: (spec.width * settings.vehicle as f64).round() as i32,
             position: Point::new(0, 0),
             route,
             itineraire,
@@ -57,40 +107,26 @@ impl Vehicle {
             velocity: velosity_type[rng.gen_range(2,4)],
             velosity_type,
             is_changed_direction: false,
+            is_turned_back: false,
             is_stopped: false,
+            advised: false,
+            has_stopped: false,
             distance_traveled: 0.0,
             stage: Stage::Waiting,
             time: 0.0,
             angle_1,
-            angle_2: match route {
-                Direction::Up => match itineraire {
-                    Itineraire::Left => 360.0,
-                    Itineraire::Right => 180.0,
-                    Itineraire::Straight => angle_1,
-                },
-                Direction::Down => match itineraire {
-                    Itineraire::Left => 180.0,
-                    Itineraire::Right => 360.0,
-                    Itineraire::Straight => angle_1,
-                },
-                Direction::Left => match itineraire {
-                    Itineraire::Left => -90.0,
-                    Itineraire::Right => 90.0,
-                    Itineraire::Straight => angle_1,
-                },
-                _ => match itineraire {
-                    Itineraire::Left => 90.0,
-                    Itineraire::Right => -90.0,
-                    Itineraire::Straight => angle_1,
-                },
+            // a U-turn heads the way of a left turn between its two turns.
+            angle_2: match itineraire {
+                Itineraire::UTurn => approach.exit(Itineraire::Left).spec().angle,
+                _ => approach.exit(itineraire).spec().angle,
             },
+            angle_3: approach.exit(itineraire).spec().angle,
 
             min_vilosity: f64::MAX,
             max_vilosity: f64::MIN,
-            prev_time: Instant::now(),
 
             settings,
-            texture: rng.gen_range(0,6), 
+            texture: class.texture(rng),
             accumulated_x: 0.0,
             accumulated_y: 0.0,
         }
@@ -100,35 +136,22 @@ impl Vehicle {
         canvas: &mut WindowCanvas,
         texture: &Texture,
     ) -> Result<(), String> {
-        let TextureQuery { width, height, .. } = texture.query();
-        let position = match self.route {
-            Direction::Up => match self.itineraire {
-                Itineraire::Right => self.position + Point::new(width as i32, height as i32),
-                _ => self.position + Point::new(width as i32, 0)
-            },
-            Direction::Down => match self.itineraire {
-                Itineraire::Left => self.position + Point::new(0, height as i32),
-                _ => self.position,
-            },
-            Direction::Left => match self.itineraire {
-                Itineraire::Left => self.position,
-                _ => self.position + Point::new(width as i32, 0)
-            },
-            _ => match self.itineraire {
-                Itineraire::Left => self.position + Point::new(width as i32, height as i32),
-                _ => self.position + Point::new(0 as i32, height as i32)
-            }
-        };
-
-        let screen_rect = Rect::new(position.x, position.y, width, height);
+        render_vehicle(canvas, texture, self.bounding_box(), self.heading())
+    }
 
-        if !self.is_changed_direction {
-            canvas.copy_ex(texture, None, screen_rect, self.angle_1, Point::new(0, 0), true, true)?;
+    /// The angle the sprite is drawn with, it changes each time the vehicle turns.
+    pub fn heading(&self) -> f64 {
+        if self.is_turned_back {
+            self.angle_3
+        } else if !self.is_changed_direction {
+            self.angle_1
         } else {
-            canvas.copy_ex(texture, None, screen_rect, self.angle_2, Point::new(0, 0), true, true)?;
+            self.angle_2
         }
-    
-        Ok(())  
+    }
+
+    pub fn texture_index(&self) -> usize {
+        self.texture
     }
 
     pub fn set_vilosity(&mut self, vehicle_type: Vilosity) {
@@ -139,21 +162,134 @@ impl Vehicle {
             Vilosity::Fast => 3,
             
         };
-        self.velocity = self.velosity_type[i];
+        self.accelerate_to(self.velosity_type[i]);
+    }
+
+    /// Stops short of any of `areas` the vehicle is not already on.
+    pub fn yield_to(&mut self, areas: &[Rect]) {
+        let now = self.bounding_box();
+        let reach = self.velocity.ceil() as i32 + 1;
+        let travel = self.travel_direction();
+        let next = Rect::new(now.x() + travel.x * reach, now.y() + travel.y * reach, now.width(), now.height());
+
+        if areas.iter().any(|area| !area.has_intersection(now) && area.has_intersection(next)) {
+            self.velocity = 0.0;
+        }
+    }
+
+    /// Seconds lost compared to driving the same distance at the fastest speed of the class.
+    pub fn delay(&self) -> f64 {
+        let fastest = self.velosity_type[self.velosity_type.len() - 1] as f64 / TICK;
+        (self.time - self.distance_traveled / fastest).max(0.0)
+    }
+
+    /// Heads for `target` as fast as the class allows, braking is immediate.
+    pub fn accelerate_to(&mut self, target: f32) {
+        if target <= self.velocity {
+            self.velocity = target;
+        } else {
+            self.velocity = (self.velocity + self.class.spec().acceleration).min(target);
+        }
+    }
+
+    /// Drives at the speed that covers `distance` in `eta` seconds, so the vehicle
+    /// gets there as the intersection frees up instead of waiting there.
+    pub fn advise(&mut self, distance: f64, eta: f64) {
+        let fastest = self.velosity_type[self.velosity_type.len() - 1];
+        let target = ((distance / eta * TICK) as f32).clamp(self.velosity_type[1], fastest);
+        if target < self.velocity {
+            self.advised = true;
+        }
+        self.accelerate_to(target);
     }
 
     pub fn adjust_velocity(&mut self, vehicles: &Vehicle) {
-        // if you are at safty distance behind a vehicle you shoud have his velocity.
-        if self.distance(vehicles) < self.settings.safety_distance + 10.0 {
-            self.velocity = vehicles.velocity;
+        // if you are at safty distance behind a vehicle you shoud have his velocity,
+        // the distance is counted from the back of the vehicle ahead.
+        let gap = self.distance(vehicles) - (vehicles.length - self.settings.vehicle) as f64;
+        if gap < self.settings.safety_distance + 10.0 {
+            let fastest = self.velosity_type[self.velosity_type.len() - 1];
+            self.accelerate_to(vehicles.velocity.min(fastest));
         }
     }
 
+    /// Unit vector of the current direction of travel.
+    pub fn travel_direction(&self) -> Point {
+        let d = self.direction as i32;
+        if self.is_turned_back {
+            return match self.route {
+                Direction::Up | Direction::Down => Point::new(0, -d),
+                _ => Point::new(-d, 0),
+            };
+        }
+
+        match (self.route, self.is_changed_direction) {
+            (Direction::Up | Direction::Down, false) => Point::new(0, d),
+            (Direction::Up | Direction::Down, true) => match self.itineraire {
+                Itineraire::Left | Itineraire::UTurn => Point::new(d, 0),
+                _ => Point::new(-d, 0),
+            },
+            (_, false) => Point::new(d, 0),
+            (_, true) => match self.itineraire {
+                Itineraire::Right => Point::new(0, d),
+                _ => Point::new(0, -d),
+            },
+        }
+    }
+
+    pub fn bounding_box(&self) -> Rect {
+        footprint(self.position, self.travel_direction(), self.settings.vehicle, self.length, self.width)
+    }
+
+    /// The parts of the road the vehicle takes: a long vehicle that has just
+    /// turned is still partly on the lane it came from.
+    pub fn footprint(&self) -> Vec<Rect> {
+        let vehicle = self.settings.vehicle;
+        let Some((turn, before)) = self.last_turn() else { return vec![self.bounding_box()] };
+
+        let travel = self.travel_direction();
+        let past = (self.position.x - turn.x) * travel.x + (self.position.y - turn.y) * travel.y;
+        let behind = self.length - vehicle - past;
+        if behind <= 0 {
+            return vec![self.bounding_box()];
+        }
+
+        vec![
+            footprint(self.position, travel, vehicle, vehicle + past, self.width),
+            footprint(turn, before, vehicle, vehicle + behind, self.width),
+        ]
+    }
+
+    /// The last turn point the vehicle went past and the direction it came from.
+    pub fn last_turn(&self) -> Option<(Point, Point)> {
+        let approach = Approach::from_direction(self.route)?;
+        let turn = approach.turn_point(self.itineraire, &self.settings)?;
+        let (dx, dy) = approach.spec().travel;
+        match (self.is_changed_direction, self.is_turned_back) {
+            (false, _) => None,
+            (true, false) => Some((turn, Point::new(dx, dy))),
+            // between the two turns a U-turn heads the way of a left turn.
+            (true, true) => {
+                let (lx, ly) = approach.exit(Itineraire::Left).spec().travel;
+                Some((approach.turn_back_point(self.itineraire, &self.settings)?, Point::new(lx, ly)))
+            }
+        }
+    }
+
+    /// Position the back of the vehicle would have as a `settings.vehicle` square,
+    /// the same as `position` for a vehicle of that length.
+    pub fn tail(&self) -> Point {
+        let behind = self.length - self.settings.vehicle;
+        let travel = self.travel_direction();
+        self.position - Point::new(travel.x * behind, travel.y * behind)
+    }
+
     pub fn has_reached_end(&self) -> bool {
-        let border_x = self.position.x < -self.settings.vehicle
-            || self.position.x > self.settings.width + self.settings.vehicle;
-        let border_y = self.position.y < -self.settings.vehicle
-            || self.position.y > self.settings.height + self.settings.vehicle;
+        let margin = self.settings.vehicle.max(self.length);
+        let border_x = self.position.x < -margin
+            || self.position.x > self.settings.width + margin;
+        let border_y = self.position.y < -margin
+            || self.position.y > self.settings.height + margin;
 
         border_x || border_y
     }
@@ -170,103 +306,168 @@ impl Vehicle {
         ((dx * dx) + (dy * dy)).sqrt()
     }
 
-    pub fn update(&mut self, canvas: &mut Canvas<Window>, texture: &Vec<Texture>) {
+    /// Moves the vehicle by one simulation tick of `dt` seconds.
+    pub fn update(&mut self, dt: f64) {
+        // close to the turn points the speed is bounded by the turning radius.
+        if let Some(approach) = Approach::from_direction(self.route) {
+            let radius = self.class.spec().turning_radius * self.settings.vehicle as f64;
+            let turns = [
+                approach.turn_point(self.itineraire, &self.settings),
+                approach.turn_back_point(self.itineraire, &self.settings),
+            ];
+            if turns.into_iter().flatten().any(|turn| self.distance_to(turn) < radius) {
+                self.velocity = self.velocity.min(self.class.turn_speed(self.settings.vehicle));
+            }
+        }
+
+        if self.stage == Stage::Waiting && self.velocity <= self.velosity_type[0] {
+            self.has_stopped = true;
+        }
         if !self.is_stopped {
-            self.move_forward();
+            self.move_forward(dt);
         }
+    }
 
+    pub fn draw(&self, canvas: &mut Canvas<Window>, texture: &[Texture]) {
         self.render(canvas, &texture[self.texture]).unwrap();
-
-        // canvas.set_draw_color(Color::GREEN);
-        // let rect = Rect::new(
-        //     self.position.x,
-        //     self.position.y,
-        //     self.settings.vehicle as u32,
-        //     self.settings.vehicle as u32,
-        // );
-        // canvas.fill_rect(rect).unwrap();
     }
 
     fn set_stage(&mut self) {
         let len = self.settings.horizontal_key_points.len();
         let y = &self.settings.horizontal_key_points;
         let x = &self.settings.vertical_key_points;
+        // the vehicle enters the intersection with its front and leaves it with its back.
+        let (front, tail) = (self.position, self.tail());
 
         match self.route {
             Direction::Up => {
                 match self.itineraire {
                     Itineraire::Left => {
-                        if self.position.y < y[len - 2] && self.position.x > x[4] {
+                        if front.y < y[len - 2] && tail.x > x[4] {
                             self.stage = Stage::Crossing;
-                        } else if self.position.x < x[4]  {
+                        } else if tail.x < x[4]  {
                             self.stage = Stage::Crossed;
                         }
                     },
                     Itineraire::Straight => {
-                        if self.position.y < y[len - 2] && self.position.y > y[4] {
+                        if front.y < y[len - 2] && tail.y > y[4] {
                             self.stage = Stage::Crossing;
-                        } else if self.position.y < y[4] {
+                        } else if tail.y < y[4] {
+                            self.stage = Stage::Crossed;
+                        }
+                    },
+                    // a right turn only merges, it is done once it has left the intersection.
+                    Itineraire::Right => {
+                        if front.y < y[len - 2] && tail.x < x[len - 2] {
+                            self.stage = Stage::Crossing;
+                        } else if tail.x > x[len - 2] {
+                            self.stage = Stage::Crossed;
+                        }
+                    },
+                    // a U-turn is done once it has driven back out of the intersection.
+                    Itineraire::UTurn => {
+                        if self.is_turned_back && tail.y > y[len - 2] {
                             self.stage = Stage::Crossed;
+                        } else if front.y < y[len - 2] {
+                            self.stage = Stage::Crossing;
                         }
                     },
-                    _ => (),
                 }
             },
             Direction::Down => {
                 match self.itineraire {
                     Itineraire::Left => {
-                        if self.position.y > y[2] && self.position.x < x[len - 4] {
+                        if front.y > y[2] && tail.x < x[len - 4] {
                             self.stage = Stage::Crossing;
-                        } else if self.position.x > x[len - 4]{
+                        } else if tail.x > x[len - 4]{
                             self.stage = Stage::Crossed;
                         }
                     },
                     Itineraire::Straight => {
-                        if self.position.y > y[2] && self.position.y < y[len - 4] {
+                        if front.y > y[2] && tail.y < y[len - 4] {
+                            self.stage = Stage::Crossing;
+                        } else if tail.y > y[len - 4] {
+                            self.stage = Stage::Crossed;
+                        }
+                    },
+                    Itineraire::Right => {
+                        if front.y > y[2] && tail.x > x[1] {
                             self.stage = Stage::Crossing;
-                        } else if self.position.y > y[len - 4] {
+                        } else if tail.x < x[1] {
+                            self.stage = Stage::Crossed;
+                        }
+                    },
+                    Itineraire::UTurn => {
+                        if self.is_turned_back && tail.y < y[1] {
                             self.stage = Stage::Crossed;
+                        } else if front.y > y[2] {
+                            self.stage = Stage::Crossing;
                         }
                     },
-                    _ => (),
                 }
             },
             Direction::Left => {
                 match self.itineraire {
                     Itineraire::Left => {
-                        if self.position.x < x[len - 2] && self.position.y < y[len - 4] {
+                        if front.x < x[len - 2] && tail.y < y[len - 4] {
                             self.stage = Stage::Crossing;
-                        } else if  self.position.y > y[len - 4] {
+                        } else if  tail.y > y[len - 4] {
                             self.stage = Stage::Crossed;
                         }
                     },
                     Itineraire::Straight => {
-                        if self.position.x < x[len - 2] && self.position.x > x[4] {
+                        if front.x < x[len - 2] && tail.x > x[4] {
+                            self.stage = Stage::Crossing;
+                        } else if tail.x < x[4] {
+                            self.stage = Stage::Crossed;
+                        }
+                    },
+                    Itineraire::Right => {
+                        if front.x < x[len - 2] && tail.y > y[1] {
                             self.stage = Stage::Crossing;
-                        } else if self.position.x < x[4] {
+                        } else if tail.y < y[1] {
+                            self.stage = Stage::Crossed;
+                        }
+                    },
+                    Itineraire::UTurn => {
+                        if self.is_turned_back && tail.x > x[len - 2] {
                             self.stage = Stage::Crossed;
+                        } else if front.x < x[len - 2] {
+                            self.stage = Stage::Crossing;
                         }
                     },
-                    _ => (),
                 }
             },
             Direction::Right => {
                 match self.itineraire {
                     Itineraire::Left => {
-                        if self.position.x > x[2] && self.position.y > y[4] {
+                        if front.x > x[2] && tail.y > y[4] {
                             self.stage = Stage::Crossing;
-                        } else if self.position.y < y[4] {
+                        } else if tail.y < y[4] {
                             self.stage = Stage::Crossed;
                         }
                     },
                     Itineraire::Straight => {
-                        if self.position.x > x[2] && self.position.x < x[len - 4] {
+                        if front.x > x[2] && tail.x < x[len - 4] {
+                            self.stage = Stage::Crossing;
+                        } else if tail.x > x[len - 4] {
+                            self.stage = Stage::Crossed;
+                        }
+                    },
+                    Itineraire::Right => {
+                        if front.x > x[2] && tail.y < y[len - 2] {
                             self.stage = Stage::Crossing;
-                        } else if self.position.x > x[len - 4] {
+                        } else if tail.y > y[len - 2] {
+                            self.stage = Stage::Crossed;
+                        }
+                    },
+                    Itineraire::UTurn => {
+                        if self.is_turned_back && tail.x < x[1] {
                             self.stage = Stage::Crossed;
+                        } else if front.x > x[2] {
+                            self.stage = Stage::Crossing;
                         }
                     },
-                    _ => (),
                 }
             },
             _ => (),
@@ -274,100 +475,31 @@ impl Vehicle {
     }
 
     pub fn spawn(&mut self, direction: Direction) {
-        let len = self.settings.vertical_key_points.len();
-        let get_position = |vp_idx, hp_idx| {
-            Point::new(
-                self.settings.vertical_key_points[vp_idx],
-                self.settings.horizontal_key_points[hp_idx],
-            )
-        };
-
-        match direction {
-            Direction::Up => {
-                self.position = match self.itineraire {
-                    Itineraire::Left => get_position(9, len - 1),
-                    Itineraire::Straight => get_position(11, len - 1),
-                    Itineraire::Right => get_position(13, len - 1),
-                };
-            }
-            Direction::Down => {
-                self.position = match self.itineraire {
-                    Itineraire::Right => get_position(3, 0),
-                    Itineraire::Straight => get_position(5, 0),
-                    Itineraire::Left => get_position(7, 0),
-                };
-            }
-            Direction::Left => {
-                self.position = match self.itineraire {
-                    Itineraire::Right => get_position(len - 1, 3),
-                    Itineraire::Straight => get_position(len - 1, 5),
-                    Itineraire::Left => get_position(len - 1, 7),
-                };
-            }
-            Direction::Right => {
-                self.position = match self.itineraire {
-                    Itineraire::Left => get_position(0, 9),
-                    Itineraire::Straight => get_position(0, 11),
-                    Itineraire::Right => get_position(0, 13),
-                };
-            }
-            _ => (),
+        if let Some(approach) = Approach::from_direction(direction) {
+            self.position = approach.spawn(self.itineraire, &self.settings);
         }
     }
 
     fn move_in_direction(&mut self) {
-        if !self.is_changed_direction {
-            match self.route {
-                Direction::Up | Direction::Down => {
-                    self.accumulated_y += self.direction * self.velocity;
-                    if self.accumulated_y.abs() >= 1.0 {
-                        let integer_part = self.accumulated_y.trunc() as i32;
-                        self.position.y += integer_part;
-                        self.accumulated_y -= integer_part as f32;
-                    }
-                }
-                Direction::Left | Direction::None | Direction::Right => {
-                    self.accumulated_x += self.direction * self.velocity;
-                    if self.accumulated_x.abs() >= 1.0 {
-                        let integer_part = self.accumulated_x.trunc() as i32;
-                        self.position.x += integer_part;
-                        self.accumulated_x -= integer_part as f32;
-                    }
-                }
-            };
+        let travel = self.travel_direction();
+        if travel.x != 0 {
+            self.accumulated_x += travel.x as f32 * self.velocity;
+            if self.accumulated_x.abs() >= 1.0 {
+                let integer_part = self.accumulated_x.trunc() as i32;
+                self.position.x += integer_part;
+                self.accumulated_x -= integer_part as f32;
+            }
         } else {
-            match self.route {
-                Direction::Up | Direction::Down => {
-                    let d = if self.itineraire == Itineraire::Left {
-                        self.direction
-                    } else {
-                        -self.direction
-                    };
-                    self.accumulated_x += d * self.velocity;
-                    if self.accumulated_x.abs() >= 1.0 {
-                        let integer_part = self.accumulated_x.trunc() as i32;
-                        self.position.x += integer_part;
-                        self.accumulated_x -= integer_part as f32;
-                    }
-                }
-                Direction::Left | Direction::None | Direction::Right => {
-                    let d = if self.itineraire == Itineraire::Right {
-                        self.direction
-                    } else {
-                        -self.direction
-                    };
-                    self.accumulated_y += d * self.velocity;
-                    if self.accumulated_y.abs() >= 1.0 {
-                        let integer_part = self.accumulated_y.trunc() as i32;
-                        self.position.y += integer_part;
-                        self.accumulated_y -= integer_part as f32;
-                    }
-                }
-            };
-        };
+            self.accumulated_y += travel.y as f32 * self.velocity;
+            if self.accumulated_y.abs() >= 1.0 {
+                let integer_part = self.accumulated_y.trunc() as i32;
+                self.position.y += integer_part;
+                self.accumulated_y -= integer_part as f32;
+            }
+        }
     }
 
-    pub fn move_forward(&mut self) {
+    pub fn move_forward(&mut self, dt: f64) {
         if self.is_stopped {
             return;
         }
@@ -381,9 +513,9 @@ impl Vehicle {
 
         // Calculate velocity
         let delta_distance = self.distance_to(prev_position);
-        let delta_time = Instant::now().duration_since(self.prev_time).as_secs_f64();
-        let velocity = delta_distance / delta_time;
-        self.time += delta_time;
+        let velocity = delta_distance / dt;
+        self.time += dt;
+        self.distance_traveled += delta_distance;
 
         // Update min and max velocity
         if velocity < self.min_vilosity {
@@ -392,55 +524,23 @@ impl Vehicle {
         if velocity > self.max_vilosity {
             self.max_vilosity = velocity;
         }
-
-        self.prev_time = Instant::now();
         
-        match self.route {
-            Direction::Up => {
-                if (prev_position.y > self.settings.horizontal_key_points[13]
-                    && self.position.y <= self.settings.horizontal_key_points[13]
-                    && self.itineraire == Itineraire::Right)
-                    || (prev_position.y > self.settings.horizontal_key_points[7]
-                        && self.position.y <= self.settings.horizontal_key_points[7]
-                        && self.itineraire == Itineraire::Left)
-                {
-                    self.is_changed_direction = true;
-                }
+        // turn once the vehicle goes past the turn point of its itineraire.
+        let Some(approach) = Approach::from_direction(self.route) else { return };
+        if let (true, Some(back)) = (self.is_changed_direction, approach.turn_back_point(self.itineraire, &self.settings)) {
+            let travel = self.travel_direction();
+            let along = |p: Point| p.x * travel.x + p.y * travel.y;
+            if along(prev_position) < along(back) && along(self.position) >= along(back) {
+                self.is_turned_back = true;
             }
-            Direction::Down => {
-                if (prev_position.y < self.settings.horizontal_key_points[3]
-                    && self.position.y >= self.settings.horizontal_key_points[3]
-                    && self.itineraire == Itineraire::Right)
-                    || (prev_position.y < self.settings.horizontal_key_points[9]
-                        && self.position.y >= self.settings.horizontal_key_points[9]
-                        && self.itineraire == Itineraire::Left)
-                {
-                    self.is_changed_direction = true;
-                }
-            }
-            Direction::Left => {
-                if (prev_position.x > self.settings.vertical_key_points[13]
-                    && self.position.x <= self.settings.vertical_key_points[13]
-                    && self.itineraire == Itineraire::Right)
-                    || (prev_position.x > self.settings.vertical_key_points[7]
-                        && self.position.x <= self.settings.vertical_key_points[7]
-                        && self.itineraire == Itineraire::Left)
-                {
-                    self.is_changed_direction = true;
-                }
-            }
-            Direction::Right => {
-                if (prev_position.x < self.settings.vertical_key_points[9]
-                    && self.position.x >= self.settings.vertical_key_points[9]
-                    && self.itineraire == Itineraire::Left)
-                    || (prev_position.x < self.settings.vertical_key_points[3]
-                        && self.position.x >= self.settings.vertical_key_points[3]
-                        && self.itineraire == Itineraire::Right)
-                {
-                    self.is_changed_direction = true;
-                }
+            return;
+        }
+        if let Some(turn) = approach.turn_point(self.itineraire, &self.settings) {
+            let (dx, dy) = approach.spec().travel;
+            let along = |p: Point| p.x * dx + p.y * dy;
+            if along(prev_position) < along(turn) && along(self.position) >= along(turn) {
+                self.is_changed_direction = true;
             }
-            _ => (),
         }
     }
 }
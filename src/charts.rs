@@ -0,0 +1,122 @@
+use sdl2::{
+    pixels::Color,
+    rect::{Point, Rect},
+    render::{TextureQuery, WindowCanvas},
+    ttf::Font,
+};
+
+use crate::Histogram;
+
+/// A named polyline of a chart: legend, colour and `(x, y)` points.
+pub type Series = (String, Color, Vec<(f64, f64)>);
+
+const AXIS_COLOR: Color = Color::RGB(200, 200, 200);
+const MARGIN: i32 = 40;
+
+pub fn draw_text(canvas: &mut WindowCanvas, font: &Font, text: &str, position: Point, color: Color) -> Result<(), String> {
+    let surface = font.render(text).blended(color).map_err(|e| e.to_string())?;
+    let texture_creator = canvas.texture_creator();
+    let texture = texture_creator
+        .create_texture_from_surface(&surface)
+        .map_err(|e| e.to_string())?;
+
+    let TextureQuery { width, height, .. } = texture.query();
+    canvas.copy(&texture, None, Rect::new(position.x, position.y, width, height))
+}
+
+/// Draws the title and the two axes, returns the rectangle left for the data.
+fn draw_frame(
+    canvas: &mut WindowCanvas,
+    font: &Font,
+    area: Rect,
+    title: &str,
+    x_label: &str,
+    y_max: f64,
+) -> Result<Rect, String> {
+    draw_text(canvas, font, title, Point::new(area.x() + MARGIN, area.y() + 4), Color::RGB(255, 255, 255))?;
+
+    let plot = Rect::new(
+        area.x() + MARGIN,
+        area.y() + MARGIN,
+        area.width() - 2 * MARGIN as u32,
+        area.height() - 2 * MARGIN as u32,
+    );
+
+    canvas.set_draw_color(AXIS_COLOR);
+    canvas.draw_line(plot.bottom_left(), plot.top_left())?;
+    canvas.draw_line(plot.bottom_left(), plot.bottom_right())?;
+
+    draw_text(canvas, font, &format!("{:.0}", y_max), Point::new(area.x() + 4, plot.top() - 8), AXIS_COLOR)?;
+    draw_text(canvas, font, "0", Point::new(area.x() + 4, plot.bottom() - 8), AXIS_COLOR)?;
+    draw_text(canvas, font, x_label, Point::new(plot.left() + plot.width() as i32 / 2 - 30, plot.bottom() + 8), AXIS_COLOR)?;
+
+    Ok(plot)
+}
+
+/// Plots each series as a polyline sharing the same axes, the legend is drawn
+/// under the title.
+pub fn draw_line_chart(
+    canvas: &mut WindowCanvas,
+    font: &Font,
+    area: Rect,
+    title: &str,
+    x_label: &str,
+    series: &[Series],
+) -> Result<(), String> {
+    let points = series.iter().flat_map(|(_, _, points)| points.iter());
+    let (x_max, y_max) = points.fold((1.0f64, 1.0f64), |(x, y), p| (x.max(p.0), y.max(p.1)));
+
+    let plot = draw_frame(canvas, font, area, title, x_label, y_max)?;
+    draw_text(canvas, font, &format!("{:.0}", x_max), Point::new(plot.right() - 20, plot.bottom() + 8), AXIS_COLOR)?;
+
+    let to_screen = |(x, y): (f64, f64)| {
+        Point::new(
+            plot.left() + (x / x_max * plot.width() as f64) as i32,
+            plot.bottom() - (y / y_max * plot.height() as f64) as i32,
+        )
+    };
+
+    for (i, (name, color, points)) in series.iter().enumerate() {
+        draw_text(canvas, font, name, Point::new(plot.left() + 20 + 110 * i as i32, plot.top() + 4), *color)?;
+
+        let screen: Vec<Point> = points.iter().map(|p| to_screen(*p)).collect();
+        canvas.set_draw_color(*color);
+        canvas.draw_lines(screen.as_slice())?;
+    }
+
+    Ok(())
+}
+
+pub fn draw_histogram(
+    canvas: &mut WindowCanvas,
+    font: &Font,
+    area: Rect,
+    title: &str,
+    unit: &str,
+    histogram: &Histogram,
+) -> Result<(), String> {
+    let y_max = histogram.counts.iter().cloned().max().unwrap_or(0).max(1);
+    let plot = draw_frame(canvas, font, area, title, unit, y_max as f64)?;
+
+    let bins = histogram.counts.len().max(1);
+    let x_max = bins as f64 * histogram.bin_width;
+    draw_text(canvas, font, &format!("{:.0}", x_max), Point::new(plot.right() - 20, plot.bottom() + 8), AXIS_COLOR)?;
+
+    let bar_width = (plot.width() / bins as u32).max(1);
+    canvas.set_draw_color(Color::RGB(90, 160, 230));
+    for (i, count) in histogram.counts.iter().enumerate() {
+        if *count == 0 {
+            continue;
+        }
+        let height = (*count as f64 / y_max as f64 * plot.height() as f64) as u32;
+        let bar = Rect::new(
+            plot.left() + (i as u32 * bar_width) as i32 + 1,
+            plot.bottom() - height as i32,
+            bar_width.saturating_sub(2).max(1),
+            height,
+        );
+        canvas.fill_rect(bar)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,128 @@
+use sdl2::{
+    pixels::Color,
+    rect::{Point, Rect},
+    render::{BlendMode, WindowCanvas},
+};
+
+use crate::{conflicts, lane::Stage, Cross, Itineraire, Route, Simulation};
+
+/// How far ahead, in ticks, the velocity vector is drawn.
+const VELOCITY_TICKS: f32 = 15.0;
+
+fn stage_color(stage: Stage, alpha: u8) -> Color {
+    match stage {
+        Stage::Crossing => Color::RGBA(60, 220, 60, alpha),
+        Stage::Crossed => Color::RGBA(60, 140, 255, alpha),
+        Stage::Waiting => Color::RGBA(255, 180, 40, alpha),
+    }
+}
+
+fn routes(simulation: &Simulation) -> impl Iterator<Item = &Route> {
+    simulation.lanes.iter().flat_map(|lane| lane.routes.iter())
+}
+
+fn key(route: &Route) -> (Cross, Itineraire) {
+    (route.cross, route.itineraire)
+}
+
+/// Common part of two axis aligned segments, as its middle point.
+fn segment_intersection(a: (Point, Point), b: (Point, Point)) -> Option<Point> {
+    let (ax0, ax1) = (a.0.x.min(a.1.x), a.0.x.max(a.1.x));
+    let (ay0, ay1) = (a.0.y.min(a.1.y), a.0.y.max(a.1.y));
+    let (bx0, bx1) = (b.0.x.min(b.1.x), b.0.x.max(b.1.x));
+    let (by0, by1) = (b.0.y.min(b.1.y), b.0.y.max(b.1.y));
+
+    let (x0, x1) = (ax0.max(bx0), ax1.min(bx1));
+    let (y0, y1) = (ay0.max(by0), ay1.min(by1));
+    if x0 > x1 || y0 > y1 {
+        return None;
+    }
+    Some(Point::new((x0 + x1) / 2, (y0 + y1) / 2))
+}
+
+/// Points where the paths of two routes meet.
+pub fn conflict_points(a: &Route, b: &Route) -> Vec<Point> {
+    let (path_a, path_b) = (a.path(), b.path());
+    let mut points = Vec::new();
+    for sa in path_a.windows(2) {
+        for sb in path_b.windows(2) {
+            if let Some(p) = segment_intersection((sa[0], sa[1]), (sb[0], sb[1])) {
+                points.push(p);
+            }
+        }
+    }
+    points
+}
+
+fn draw_circle(canvas: &mut WindowCanvas, center: Point, radius: f64) -> Result<(), String> {
+    let points: Vec<Point> = (0..=48)
+        .map(|i| {
+            let angle = i as f64 / 48.0 * std::f64::consts::PI * 2.0;
+            Point::new(
+                center.x + (radius * angle.cos()) as i32,
+                center.y + (radius * angle.sin()) as i32,
+            )
+        })
+        .collect();
+    canvas.draw_lines(points.as_slice())
+}
+
+/// Draws what the intersection algorithm works with on top of the map.
+pub fn draw_debug_overlay(canvas: &mut WindowCanvas, simulation: &Simulation) -> Result<(), String> {
+    let half = simulation.settings.vehicle / 2;
+    let center = |p: Point| p + Point::new(half, half);
+    canvas.set_blend_mode(BlendMode::Blend);
+
+    // conflict zones: where the path of each BLOCK lane meets the routes it conflicts with.
+    canvas.set_draw_color(Color::RGBA(255, 0, 0, 50));
+    for route in routes(simulation) {
+        for other in routes(simulation).filter(|other| conflicts(key(route), key(other))) {
+            for p in conflict_points(route, other) {
+                let zone = simulation.settings.vehicle as u32;
+                canvas.fill_rect(Rect::from_center(center(p), zone, zone))?;
+            }
+        }
+    }
+
+    // the stop point of each route coloured by the route stage.
+    for route in simulation.lanes.iter().flat_map(|lane| lane.routes.iter()) {
+        canvas.set_draw_color(stage_color(route.stage, 255));
+        let anchor = center(route.stop_anchor());
+        let (dx, dy) = match route.cross {
+            Cross::First | Cross::Fourth => (half, 0),
+            Cross::Second | Cross::Third => (0, half),
+        };
+        canvas.draw_line(anchor - Point::new(dx, dy), anchor + Point::new(dx, dy))?;
+        canvas.fill_rect(Rect::from_center(anchor, 6, 6))?;
+    }
+
+    // lines between a waiting route and the crossing routes holding it.
+    canvas.set_draw_color(Color::RGBA(255, 60, 60, 255));
+    for route in routes(simulation).filter(|route| route.stage != Stage::Crossing && !route.vehicles.is_empty()) {
+        for other in routes(simulation).filter(|other| other.stage == Stage::Crossing && conflicts(key(route), key(other))) {
+            canvas.draw_line(center(route.stop_anchor()), center(other.stop_anchor()))?;
+        }
+    }
+
+    for vehicle in simulation.lanes.iter().flat_map(|l| l.routes.iter()).flat_map(|r| r.vehicles.iter()) {
+        let bounding_box = vehicle.bounding_box();
+        let middle = bounding_box.center();
+
+        canvas.set_draw_color(stage_color(vehicle.stage, 90));
+        canvas.fill_rect(bounding_box)?;
+        canvas.set_draw_color(stage_color(vehicle.stage, 255));
+        canvas.draw_rect(bounding_box)?;
+
+        let direction = vehicle.travel_direction();
+        let length = vehicle.velocity * VELOCITY_TICKS;
+        let tip = middle + Point::new((direction.x as f32 * length) as i32, (direction.y as f32 * length) as i32);
+        canvas.set_draw_color(Color::RGBA(255, 255, 255, 255));
+        canvas.draw_line(middle, tip)?;
+
+        canvas.set_draw_color(Color::RGBA(255, 255, 255, 70));
+        draw_circle(canvas, middle, simulation.settings.safety_distance)?;
+    }
+
+    canvas.set_blend_mode(BlendMode::None);
+    Ok(())
+}
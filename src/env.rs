@@ -0,0 +1,191 @@
+use std::rc::Rc;
+
+use rand::{SeedableRng, StdRng};
+
+use crate::{
+    experiment::arrive,
+    json::Json,
+    lane::Stage,
+    replay::{cross_from_str, itineraire_from_str, text},
+    Controller, Cross, Itineraire, Settings, Simulation, TICK,
+};
+
+/// What a collision costs the reward, in vehicle-seconds of waiting.
+const COLLISION_PENALTY: f64 = 100.0;
+
+/// What the agent sees of a route.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteObservation {
+    pub route: (Cross, Itineraire),
+    pub stage: Stage,
+    /// Pedestrians are on the crosswalk of the leg it leaves by: a grant lets
+    /// none of its vehicles in until they are across.
+    pub held: bool,
+    /// Vehicles before the stop line.
+    pub queue: usize,
+    /// Pixels from each of them to the stop point of the route, the head first.
+    pub distances: Vec<f64>,
+}
+
+/// The state of the intersection after a reset or a step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Observation {
+    /// Simulation time in seconds.
+    pub time: f64,
+    /// Every route, in the order of the lanes.
+    pub routes: Vec<RouteObservation>,
+}
+
+/// The routes to give the intersection. A route keeps it until its vehicles are
+/// through, as with the other controllers; granting two routes whose paths
+/// cross is allowed and shows in the reward.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Action {
+    pub grants: Vec<(Cross, Itineraire)>,
+}
+
+/// The headless simulation as a reinforcement learning environment: the agent
+/// is the controller, deciding every `interval` seconds which routes get the
+/// intersection while the vehicles arrive as in an `Experiment`.
+pub struct Environment {
+    pub settings: Rc<Settings>,
+    /// Vehicles arriving on each approach per minute.
+    pub demand: f64,
+    /// Simulated seconds per episode.
+    pub duration: f64,
+    /// Simulated seconds between two decisions of the agent.
+    pub interval: f64,
+    pub simulation: Simulation,
+    rng: StdRng,
+}
+
+impl Environment {
+    pub fn new(settings: Rc<Settings>, demand: f64, duration: f64, interval: f64) -> Environment {
+        let mut simulation = Simulation::with_seed(settings.clone(), Controller::External, 0);
+        simulation.check_invariants = false;
+        Environment { settings, demand, duration, interval, simulation, rng: SeedableRng::from_seed(&[0][..]) }
+    }
+
+    /// Starts a new episode, `seed` drawing the arrivals, their turn and their
+    /// class, and seeding the simulation: the same seed and actions always give
+    /// the same episode.
+    pub fn reset(&mut self, seed: usize) -> Observation {
+        self.simulation = Simulation::with_seed(self.settings.clone(), Controller::External, seed);
+        self.simulation.check_invariants = false;
+        self.rng = SeedableRng::from_seed(&[seed][..]);
+        self.observe()
+    }
+
+    /// Gives the intersection to the routes of `action` and simulates `interval`
+    /// seconds. The reward is minus the vehicle-seconds spent before the stop
+    /// lines over the step, and `COLLISION_PENALTY` per collision; the episode is
+    /// done once `duration` seconds are simulated.
+    pub fn step(&mut self, action: &Action) -> (Observation, f64, bool) {
+        for route in self.simulation.lanes.iter_mut().flat_map(|lane| lane.routes.iter_mut()) {
+            if route.stage != Stage::Crossing && !route.vehicles.is_empty() && action.grants.contains(&(route.cross, route.itineraire)) {
+                route.time = 0.0;
+                route.stage = Stage::Crossing;
+            }
+        }
+
+        let collisions = self.simulation.statistic.collisions;
+        let mut reward = 0.0;
+        for _ in 0..((self.interval / TICK).round() as usize).max(1) {
+            arrive(&mut self.simulation, &mut self.rng, self.demand);
+            self.simulation.tick();
+            let waiting = self.simulation.lanes
+                .iter()
+                .flat_map(|lane| lane.routes.iter())
+                .flat_map(|route| route.vehicles.iter())
+                .filter(|v| v.stage == Stage::Waiting)
+                .count();
+            reward -= waiting as f64 * TICK;
+        }
+        reward -= (self.simulation.statistic.collisions - collisions) as f64 * COLLISION_PENALTY;
+
+        // half a tick of slack for the rounding of the simulation time.
+        (self.observe(), reward, self.simulation.time >= self.duration - TICK / 2.0)
+    }
+
+    pub fn observe(&self) -> Observation {
+        let routes = self.simulation.lanes
+            .iter()
+            .flat_map(|lane| lane.routes.iter())
+            .map(|route| {
+                let distances: Vec<f64> = route.vehicles
+                    .iter()
+                    .filter(|v| v.stage == Stage::Waiting)
+                    .map(|v| route.to_stop_line(v))
+                    .collect();
+                RouteObservation {
+                    route: (route.cross, route.itineraire),
+                    stage: route.stage,
+                    held: route.is_held(),
+                    queue: distances.len(),
+                    distances,
+                }
+            })
+            .collect();
+        Observation { time: self.simulation.time, routes }
+    }
+
+    /// Answers one line of the protocol of `smart-road-env`:
+    /// `{"op":"reset","seed":0}` with `{"observation":...}`, and
+    /// `{"op":"step","grants":[{"cross":"First","itineraire":"Left"}]}` with
+    /// `{"observation":...,"reward":...,"done":...}`. A request it can't read
+    /// gets `{"error":...}`.
+    pub fn respond(&mut self, line: &str) -> Json {
+        let answer = Json::parse(line).and_then(|request| {
+            match request.field("op")?.as_str() {
+                Some("reset") => {
+                    let seed = request.get("seed").map_or(Some(0.0), Json::as_f64).ok_or("`seed` is not a number")?;
+                    Ok(Json::Object(vec![("observation".into(), self.reset(seed as usize).to_json())]))
+                }
+                Some("step") => {
+                    let (observation, reward, done) = self.step(&Action::from_json(&request)?);
+                    Ok(Json::Object(vec![
+                        ("observation".into(), observation.to_json()),
+                        ("reward".into(), Json::Number(reward)),
+                        ("done".into(), Json::Bool(done)),
+                    ]))
+                }
+                _ => Err(format!("unknown op {}, use reset or step", request.field("op")?)),
+            }
+        });
+        answer.unwrap_or_else(|e| Json::Object(vec![("error".into(), Json::String(e))]))
+    }
+}
+
+impl Observation {
+    pub fn to_json(&self) -> Json {
+        let routes = self.routes
+            .iter()
+            .map(|r| Json::Object(vec![
+                ("cross".into(), text(r.route.0)),
+                ("itineraire".into(), text(r.route.1)),
+                ("stage".into(), text(r.stage)),
+                ("held".into(), Json::Bool(r.held)),
+                ("queue".into(), Json::Number(r.queue as f64)),
+                ("distances".into(), Json::Array(r.distances.iter().map(|d| Json::Number(*d)).collect())),
+            ]))
+            .collect();
+        Json::Object(vec![("t".into(), Json::Number(self.time)), ("routes".into(), Json::Array(routes))])
+    }
+}
+
+impl Action {
+    /// Reads the `grants` of a step request, no grants when it has none.
+    pub fn from_json(json: &Json) -> Result<Action, String> {
+        let Some(grants) = json.get("grants") else { return Ok(Action::default()) };
+        let grants = grants
+            .as_array()
+            .ok_or("`grants` is not an array")?
+            .iter()
+            .map(|grant| {
+                let field = |key: &str| grant.field(key)?.as_str().ok_or_else(|| format!("`{}` is not a string", key));
+                Ok((cross_from_str(field("cross")?)?, itineraire_from_str(field("itineraire")?)?))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Action { grants })
+    }
+}
@@ -0,0 +1,114 @@
+use std::rc::Rc;
+
+use rand::{Rng, SeedableRng, StdRng};
+
+use crate::{Controller, Estimate, Itineraire, Settings, Simulation, Statistics, VehicleClass, TICK};
+
+/// One configuration of a batch of headless runs: a controller under a demand.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Experiment {
+    pub controller: Controller,
+    /// Vehicles arriving on each approach per minute.
+    pub demand: f64,
+    /// Simulated seconds per run.
+    pub duration: f64,
+}
+
+/// The statistics of one run of an experiment.
+#[derive(Debug, Clone)]
+pub struct Run {
+    pub seed: usize,
+    /// Seconds of the run within the measurement window of the settings.
+    pub duration: f64,
+    pub statistic: Statistics,
+}
+
+impl Experiment {
+    /// Simulates `duration` seconds with the vehicles arriving at random on each
+    /// approach, as often as `demand` says. `seed` draws the arrivals, their turn
+    /// and their class, and seeds the simulation: a seed always gives the same run.
+    pub fn run(&self, settings: &Rc<Settings>, seed: usize) -> Run {
+        let mut simulation = Simulation::with_seed(settings.clone(), self.controller, seed);
+        simulation.check_invariants = false;
+        let mut rng: StdRng = SeedableRng::from_seed(&[seed][..]);
+        for _ in 0..(self.duration / TICK) as usize {
+            arrive(&mut simulation, &mut rng, self.demand);
+            simulation.tick();
+        }
+
+        let (from, to) = simulation.statistic.window;
+        let duration = (self.duration.min(to) - from).max(0.0);
+        Run { seed, duration, statistic: simulation.statistic }
+    }
+}
+
+/// Spawns the vehicles arriving in one tick on each approach, `demand` a minute,
+/// with a turn and a class drawn from `rng`.
+pub(crate) fn arrive(simulation: &mut Simulation, rng: &mut StdRng, demand: f64) {
+    let turns = [Itineraire::Left, Itineraire::Straight, Itineraire::Right, Itineraire::UTurn];
+    let chance = demand / 60.0 * TICK;
+    for lane in simulation.lanes.iter_mut() {
+        if rng.gen::<f64>() < chance {
+            let turn = turns[rng.gen_range(0, turns.len())];
+            lane.add_vehicle(turn, Some(VehicleClass::pick_with(&simulation.settings.mix, rng)), &mut simulation.rng);
+        }
+    }
+}
+
+/// A number read off the statistics of every run.
+pub struct Measure {
+    pub name: &'static str,
+    pub read: fn(&Run) -> f64,
+}
+
+impl Measure {
+    pub const ALL: [Measure; 8] = [
+        Measure {
+            name: "throughput (veh/min)",
+            read: |run| if run.duration > 0.0 { run.statistic.max_vehicles_passed as f64 * 60.0 / run.duration } else { 0.0 },
+        },
+        Measure { name: "time to pass (s)", read: |run| run.statistic.time_to_pass.mean },
+        Measure { name: "p90 time to pass (s)", read: |run| run.statistic.time_to_pass.p90() },
+        Measure { name: "speed (px/s)", read: |run| run.statistic.speed.mean },
+        Measure { name: "advised, not stopped", read: |run| run.statistic.stops_avoided as f64 },
+        Measure { name: "close calls", read: |run| run.statistic.close_calls as f64 },
+        Measure { name: "collisions", read: |run| run.statistic.collisions as f64 },
+        Measure { name: "truncated schedules", read: |run| run.statistic.truncated_schedules as f64 },
+    ];
+
+    pub fn estimate(&self, runs: &[Run]) -> Estimate {
+        Estimate::of(&runs.iter().map(self.read).collect::<Vec<f64>>())
+    }
+}
+
+/// A markdown table with a row per experiment: every measure as its mean over
+/// the runs and the half width of its 95% confidence interval.
+pub fn summary_table(results: &[(Experiment, Vec<Run>)]) -> String {
+    let mut header = vec!["controller".to_string(), "demand (veh/min)".to_string(), "runs".to_string()];
+    header.extend(Measure::ALL.iter().map(|m| m.name.to_string()));
+
+    let mut rows = vec![header];
+    for (experiment, runs) in results {
+        let mut row = vec![experiment.controller.name().to_string(), format!("{}", experiment.demand), runs.len().to_string()];
+        for measure in Measure::ALL.iter() {
+            let estimate = measure.estimate(runs);
+            row.push(format!("{:.2} ± {:.2}", estimate.mean, estimate.half_width));
+        }
+        rows.push(row);
+    }
+
+    let widths: Vec<usize> = (0..rows[0].len())
+        .map(|i| rows.iter().map(|row| row[i].chars().count()).max().unwrap_or(0))
+        .collect();
+    let line = |row: &Vec<String>| -> String {
+        let cells: Vec<String> = row.iter().zip(widths.iter()).map(|(cell, w)| format!("{:<w$}", cell, w = w)).collect();
+        format!("| {} |\n", cells.join(" | "))
+    };
+
+    let mut table = line(&rows[0]);
+    table += &line(&widths.iter().map(|w| "-".repeat(*w)).collect());
+    for row in rows.iter().skip(1) {
+        table += &line(row);
+    }
+    table
+}
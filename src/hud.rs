@@ -0,0 +1,182 @@
+use sdl2::{
+    pixels::Color,
+    rect::Rect,
+    render::{BlendMode, TextureQuery, WindowCanvas},
+    ttf::Font,
+};
+
+use crate::{lane::Stage, Clock, Cross, Inspector, Itineraire, Replay, Simulation};
+
+const PADDING: i32 = 10;
+const LINE_HEIGHT: i32 = 20;
+const COLUMN_WIDTH: i32 = 70;
+const WIDTH: u32 = 300;
+
+fn stage_name(stage: Stage) -> &'static str {
+    match stage {
+        Stage::Crossing => "Crossing",
+        Stage::Crossed => "Crossed",
+        Stage::Waiting => "Waiting",
+    }
+}
+
+fn stage_color(stage: Stage) -> Color {
+    match stage {
+        Stage::Crossing => Color::RGB(120, 230, 120),
+        Stage::Crossed => Color::RGB(120, 180, 255),
+        Stage::Waiting => Color::RGB(255, 200, 90),
+    }
+}
+
+/// Draws the live statistics panel in the top left corner of the map.
+pub fn draw_hud(canvas: &mut WindowCanvas, font: &Font, simulation: &Simulation, clock: &Clock) -> Result<(), String> {
+    let (lanes, statistic, sim_time) = (&simulation.lanes, &simulation.statistic, simulation.time);
+    let in_system: usize = lanes
+        .iter()
+        .flat_map(|lane| lane.routes.iter())
+        .map(|route| route.vehicles.len())
+        .sum();
+    let throughput = if sim_time > 0.0 {
+        statistic.max_vehicles_passed as f64 * 60.0 / sim_time
+    } else {
+        0.0
+    };
+
+    let white = Color::RGB(255, 255, 255);
+    let mut lines: Vec<Vec<(String, Color)>> = vec![
+        vec![(format!("Time : {:.1} s", sim_time), white)],
+        vec![(format!("Speed : x{}{}", clock.scale(), if clock.paused { " (paused)" } else { "" }), white)],
+        vec![(format!("Vehicles in system : {}", in_system), white)],
+        vec![(format!("Throughput : {:.1} veh/min", throughput), white)],
+        vec![(format!("Close calls : {}", statistic.close_calls), white)],
+        vec![(format!("Collisions : {}", statistic.collisions), white)],
+        vec![(format!("Controller : {}", simulation.controller.name()), white)],
+    ];
+
+    let waiting: usize = simulation.crosswalks.iter().map(|c| c.waiting()).sum();
+    lines.push(vec![(format!("Pedestrians waiting : {}", waiting), white)]);
+
+    let longest = lanes.iter().flat_map(|lane| lane.routes.iter()).map(|r| r.waited(sim_time)).fold(0.0, f64::max);
+    lines.push(vec![(format!("Longest route wait : {:.1} s", longest), white)]);
+
+    let held = lanes.iter().flat_map(|lane| lane.routes.iter()).filter(|r| r.preempted).count();
+    if held > 0 {
+        lines.push(vec![(format!("Emergency pre-emption : {} routes held", held), Color::RGB(255, 90, 90))]);
+    }
+
+    lines.extend(stage_lines(
+        lanes.iter().flat_map(|lane| lane.routes.iter()).map(|r| (r.cross, r.itineraire, r.stage)),
+    ));
+
+    draw_panel(canvas, font, &lines)
+}
+
+/// Draws the recorded time, the playback state and the recorded route stages.
+pub fn draw_replay_hud(canvas: &mut WindowCanvas, font: &Font, replay: &Replay) -> Result<(), String> {
+    let white = Color::RGB(255, 255, 255);
+    let frame = replay.frame();
+    let state = if replay.paused { "paused" } else { "playing" };
+
+    let mut lines: Vec<Vec<(String, Color)>> = vec![
+        vec![(format!("Replay : {:.2} / {:.1} s", frame.time, replay.duration()), white)],
+        vec![(format!("Speed : x{} ({})", replay.speed, state), white)],
+        vec![(format!("Vehicles in system : {}", frame.vehicles.len()), white)],
+        vec![(String::from("space pause  ,/. step  arrows seek/speed"), white)],
+    ];
+    lines.extend(stage_lines(frame.routes.iter().map(|r| (r.cross, r.itineraire, r.stage))));
+
+    draw_panel(canvas, font, &lines)
+}
+
+/// Draws the fields of the vehicle picked with the mouse in the top right corner,
+/// with the turn given to the vehicles spawned by a click.
+pub fn draw_inspector(canvas: &mut WindowCanvas, font: &Font, simulation: &Simulation, inspector: &Inspector) -> Result<(), String> {
+    let white = Color::RGB(255, 255, 255);
+    let turn = match inspector.turn {
+        Some(itineraire) => format!("{:?}", itineraire),
+        None => String::from("clicked lane"),
+    };
+    let mut lines: Vec<Vec<(String, Color)>> = vec![
+        vec![(format!("Click spawn : {}", turn), white)],
+        vec![(String::from("0 lane  1 left  2 straight  3 right  4 u-turn"), white)],
+    ];
+
+    match inspector.selected_vehicle(simulation) {
+        Some(vehicle) => {
+            canvas.set_draw_color(Color::RGB(255, 255, 0));
+            canvas.draw_rect(vehicle.bounding_box())?;
+            // the speeds seen by the vehicle, none before it has moved.
+            let speed = |speed: f64| if vehicle.time > 0.0 { format!("{:.1} px/s", speed) } else { String::from("-") };
+
+            lines.extend(vec![
+                vec![(format!("Vehicle #{} ({})", vehicle.id, vehicle.class.name()), white)],
+                vec![(format!("Stage : {}", stage_name(vehicle.stage)), stage_color(vehicle.stage))],
+                vec![(format!("Velocity : {:.2} px/tick", vehicle.velocity), white)],
+                vec![(format!("Itineraire : {:?} from {:?}", vehicle.itineraire, vehicle.route), white)],
+                vec![(format!("Time : {:.2} s", vehicle.time), white)],
+                vec![(format!("Distance traveled : {:.0} px", vehicle.distance_traveled), white)],
+                vec![(format!("Min speed : {}", speed(vehicle.min_vilosity)), white)],
+                vec![(format!("Max speed : {}", speed(vehicle.max_vilosity)), white)],
+            ]);
+        }
+        None if inspector.selected.is_some() => lines.push(vec![(String::from("Vehicle has left the map"), white)]),
+        None => lines.push(vec![(String::from("Click a vehicle to inspect it"), white)]),
+    }
+
+    let (width, _) = canvas.output_size()?;
+    draw_panel_at(canvas, font, &lines, width as i32 - WIDTH as i32)
+}
+
+/// One line per approach, each route stage coloured in the order the routes come.
+fn stage_lines(routes: impl Iterator<Item = (Cross, Itineraire, Stage)>) -> Vec<Vec<(String, Color)>> {
+    let white = Color::RGB(255, 255, 255);
+    let mut lines: Vec<Vec<(String, Color)>> = Vec::new();
+    let mut current = None;
+
+    for (cross, itineraire, stage) in routes {
+        if current != Some(cross) {
+            lines.push(vec![(cross.name().to_string(), white)]);
+            current = Some(cross);
+        }
+        let itineraire = match itineraire {
+            Itineraire::Left => "L",
+            Itineraire::Straight => "S",
+            Itineraire::Right => "R",
+            Itineraire::UTurn => "U",
+        };
+        if let Some(line) = lines.last_mut() {
+            line.push((format!("{} {}", itineraire, stage_name(stage)), stage_color(stage)));
+        }
+    }
+
+    lines
+}
+
+fn draw_panel(canvas: &mut WindowCanvas, font: &Font, lines: &[Vec<(String, Color)>]) -> Result<(), String> {
+    draw_panel_at(canvas, font, lines, 0)
+}
+
+fn draw_panel_at(canvas: &mut WindowCanvas, font: &Font, lines: &[Vec<(String, Color)>], left: i32) -> Result<(), String> {
+    let height = (lines.len() as i32 * LINE_HEIGHT + 2 * PADDING) as u32;
+    canvas.set_blend_mode(BlendMode::Blend);
+    canvas.set_draw_color(Color::RGBA(0, 0, 0, 170));
+    canvas.fill_rect(Rect::new(left, 0, WIDTH, height))?;
+    canvas.set_blend_mode(BlendMode::None);
+
+    let texture_creator = canvas.texture_creator();
+    for (i, line) in lines.iter().enumerate() {
+        for (j, (text, color)) in line.iter().enumerate() {
+            let surface = font.render(text).blended(*color).map_err(|e| e.to_string())?;
+            let texture = texture_creator
+                .create_texture_from_surface(&surface)
+                .map_err(|e| e.to_string())?;
+
+            let TextureQuery { width, height, .. } = texture.query();
+            let x = left + PADDING + COLUMN_WIDTH * j as i32;
+            let r = Rect::new(x, PADDING + LINE_HEIGHT * i as i32, width, height);
+            canvas.copy(&texture, None, r)?;
+        }
+    }
+
+    Ok(())
+}
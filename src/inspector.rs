@@ -0,0 +1,90 @@
+use sdl2::{
+    event::Event,
+    keyboard::Keycode,
+    mouse::MouseButton,
+    rect::{Point, Rect},
+};
+
+use crate::{Itineraire, Route, Simulation, Vehicle};
+
+/// Mouse driven state: the vehicle shown in the inspector panel and the turn
+/// given to vehicles spawned with a click.
+#[derive(Debug, Clone, Default)]
+pub struct Inspector {
+    pub selected: Option<usize>,
+    /// `None` spawns on the route of the clicked lane.
+    pub turn: Option<Itineraire>,
+}
+
+impl Inspector {
+    pub fn new() -> Inspector {
+        Inspector::default()
+    }
+
+    pub fn selected_vehicle<'a>(&self, simulation: &'a Simulation) -> Option<&'a Vehicle> {
+        let id = self.selected?;
+        simulation.lanes
+            .iter()
+            .flat_map(|lane| lane.routes.iter())
+            .flat_map(|route| route.vehicles.iter())
+            .find(|v| v.id == id)
+    }
+
+    /// Handles the clicks and the turn hotkeys, returns true if the event was used.
+    pub fn handle_event(&mut self, event: &Event, simulation: &mut Simulation) -> bool {
+        match event {
+            Event::KeyDown { keycode: Some(Keycode::Num0), .. } => self.turn = None,
+            Event::KeyDown { keycode: Some(Keycode::Num1), .. } => self.turn = Some(Itineraire::Left),
+            Event::KeyDown { keycode: Some(Keycode::Num2), .. } => self.turn = Some(Itineraire::Straight),
+            Event::KeyDown { keycode: Some(Keycode::Num3), .. } => self.turn = Some(Itineraire::Right),
+            Event::KeyDown { keycode: Some(Keycode::Num4), .. } => self.turn = Some(Itineraire::UTurn),
+            Event::MouseButtonDown { mouse_btn: MouseButton::Left, x, y, .. } => self.click(Point::new(*x, *y), simulation),
+            _ => return false,
+        }
+        true
+    }
+
+    fn click(&mut self, point: Point, simulation: &mut Simulation) {
+        let clicked = simulation.lanes
+            .iter()
+            .flat_map(|lane| lane.routes.iter())
+            .flat_map(|route| route.vehicles.iter())
+            .find(|v| v.bounding_box().contains_point(point))
+            .map(|v| v.id);
+        if clicked.is_some() {
+            self.selected = clicked;
+            return;
+        }
+
+        let entry = simulation.lanes
+            .iter()
+            .flat_map(|lane| lane.routes.iter())
+            .find(|route| entry_area(route).contains_point(point))
+            .map(|route| (route.cross, route.itineraire));
+
+        match entry {
+            Some((cross, itineraire)) => {
+                let itineraire = self.turn.unwrap_or(itineraire);
+                if let Some(lane) = simulation.lanes.iter_mut().find(|lane| lane.cross == cross) {
+                    lane.add_vehicle(itineraire, None, &mut simulation.rng);
+                }
+            }
+            None => self.selected = None,
+        }
+    }
+}
+
+/// The stretch of lane between the spawn point of a route and its stop line.
+pub fn entry_area(route: &Route) -> Rect {
+    let half = route.settings.vehicle / 2;
+    let width = route.settings.offset_road;
+    let (start, stop) = (route.path()[0], route.stop_anchor());
+    let (x0, x1) = (start.x.min(stop.x), start.x.max(stop.x));
+    let (y0, y1) = (start.y.min(stop.y), start.y.max(stop.y));
+
+    if x0 == x1 {
+        Rect::new(x0 + half - width / 2, y0, width as u32, (y1 - y0 + 2 * half) as u32)
+    } else {
+        Rect::new(x0, y0 + half - width / 2, (x1 - x0 + 2 * half) as u32, width as u32)
+    }
+}
@@ -0,0 +1,68 @@
+use crate::{conflicts, lane::Stage, routes::leader, Frame, Lane, Route, Vehicle};
+
+fn overlaps(a: &Vehicle, b: &Vehicle) -> bool {
+    a.footprint().iter().any(|ra| b.footprint().iter().any(|rb| ra.has_intersection(*rb)))
+}
+
+/// A vehicle of the route between its stop line and the exit of the intersection.
+fn inside(route: &Route) -> Option<&Vehicle> {
+    route.vehicles.iter().find(|v| v.stage == Stage::Crossing)
+}
+
+fn describe(vehicle: &Vehicle) -> String {
+    format!(
+        "vehicle {} ({:?} {:?} {:?}) at ({}, {}) going {:.2}",
+        vehicle.id, vehicle.class, vehicle.route, vehicle.itineraire,
+        vehicle.position.x, vehicle.position.y, vehicle.velocity
+    )
+}
+
+/// What the controller must never let happen, checked on the state after a tick:
+/// - two routes whose paths cross have a vehicle in the intersection at once,
+/// - a vehicle overlaps the one it follows, on its route or on another route
+///   sharing its lane,
+/// - a vehicle drives faster than its class allows, or backwards.
+///
+/// The error names the broken invariant and ends with a snapshot of the tick
+/// as a replay frame, one JSON line `--replay` can load.
+pub fn check_invariants(lanes: &[Lane], time: f64) -> Result<(), String> {
+    let fail = |message: String| {
+        Err(format!("safety invariant broken at {:.3}s: {}\n{}", time, message, Frame::capture(lanes, time).to_json()))
+    };
+
+    let routes = || lanes.iter().flat_map(|lane| lane.routes.iter());
+    for route in routes() {
+        let Some(vehicle) = inside(route) else { continue };
+        for other in routes().filter(|other| conflicts((route.cross, route.itineraire), (other.cross, other.itineraire))) {
+            if let Some(other) = inside(other) {
+                return fail(format!("{} and {} are in the intersection on conflicting routes", describe(vehicle), describe(other)));
+            }
+        }
+    }
+
+    for route in lanes.iter().flat_map(|lane| lane.routes.iter()) {
+        let others: Vec<&Vehicle> = lanes
+            .iter()
+            .flat_map(|lane| lane.routes.iter())
+            .filter(|other| (other.cross, other.itineraire) != (route.cross, route.itineraire))
+            .flat_map(|other| other.vehicles.iter())
+            .collect();
+        for (i, vehicle) in route.vehicles.iter().enumerate() {
+            if let Some(ahead) = leader(vehicle, &route.vehicles[..i], &others).filter(|ahead| overlaps(ahead, vehicle)) {
+                return fail(format!("{} runs into {} ahead of it", describe(vehicle), describe(ahead)));
+            }
+        }
+
+        for vehicle in route.vehicles.iter() {
+            let limit = vehicle.velosity_type.iter().cloned().fold(0.0, f32::max);
+            if vehicle.velocity < 0.0 {
+                return fail(format!("{} is going backwards", describe(vehicle)));
+            }
+            if vehicle.velocity > limit {
+                return fail(format!("{} is over the speed limit of {:.2}", describe(vehicle), limit));
+            }
+        }
+    }
+
+    Ok(())
+}
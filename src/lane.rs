@@ -1,10 +1,12 @@
 
 use std::rc::Rc;
 
-use crate::{Itineraire, Route, Statistics};
+use rand::Rng;
+
+use crate::{Approach, Direction, Itineraire, Route, Statistics, Vehicle, VehicleClass};
 use crate::settings::Settings;
 use sdl2::render::Texture;
-use sdl2::{rect::Point, render::Canvas, video::Window};
+use sdl2::{rect::{Point, Rect}, render::Canvas, video::Window};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Cross {
@@ -14,6 +16,17 @@ pub enum Cross {
     Fourth,
 }
 
+impl Cross {
+    /// The direction the vehicles coming from this side move toward.
+    pub fn direction(&self) -> Direction {
+        Approach::from(*self).direction()
+    }
+
+    pub fn name(&self) -> &'static str {
+        Approach::from(*self).name()
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Stage {
     Crossing,
@@ -32,18 +45,14 @@ pub struct Lane {
 
 impl Lane {
     pub fn new(cross: Cross, settings: Rc<Settings>) -> Lane {
-        let  stop_point = match cross {
-            Cross::First => Point::new(0, settings.horizontal_key_points[2]),
-            Cross::Second => Point::new(settings.vertical_key_points[2], 0),
-            Cross::Third => Point::new(settings.vertical_key_points[settings.vertical_key_points.len() - 2], 0),
-            Cross::Fourth => Point::new(0, settings.horizontal_key_points[settings.horizontal_key_points.len() - 2]),
-        };
+        let stop_point = Approach::from(cross).stop_point(&settings);
 
         Lane {
             routes: vec![
                 Route::new(Itineraire::Left, cross, stop_point, settings.clone()),
                 Route::new(Itineraire::Straight, cross, stop_point, settings.clone()),
                 Route::new(Itineraire::Right, cross, stop_point, settings.clone()),
+                Route::new(Itineraire::UTurn, cross, stop_point, settings.clone()),
             ],
             cross,
             stage: Stage::Waiting,
@@ -52,9 +61,62 @@ impl Lane {
         }
     }
 
-    pub fn update(&mut self, canvas: &mut Canvas<Window>, texture: &Vec<Texture>, statistic: &mut Statistics) {
+    /// `crosswalks` are the crosswalks pedestrians are using, no vehicle drives onto them,
+    /// `vehicles` all the vehicles of the intersection as they were before the update.
+    pub fn update<R: Rng>(&mut self, statistic: &mut Statistics, crosswalks: &[Rect], vehicles: &[Vehicle], time: f64, dt: f64, rng: &mut R) {
         for i in (0..self.routes.len()).rev() {
-            self.routes[i].update(canvas, &texture, statistic);
+            self.routes[i].update(statistic, crosswalks, vehicles, time, dt, rng);
+        }
+    }
+
+    /// The vehicles of the other routes spawning on the same inbound lane as route `i`.
+    fn shared_vehicles(&self, i: usize) -> Vec<Vehicle> {
+        let start = self.routes[i].path()[0];
+        self.routes
+            .iter()
+            .enumerate()
+            .filter(|(j, route)| *j != i && route.path()[0] == start)
+            .flat_map(|(_, route)| route.vehicles.iter().cloned())
+            .collect()
+    }
+
+    /// The route of the vehicle heading the queue on the inbound lane of `itineraire`:
+    /// the left turns and U-turns share one, whatever its route a vehicle waits
+    /// behind the one ahead.
+    pub fn head_of_lane(&self, itineraire: Itineraire) -> Itineraire {
+        let Some(i) = self.routes.iter().position(|r| r.itineraire == itineraire) else { return itineraire };
+        let (start, anchor) = (self.routes[i].path()[0], self.routes[i].stop_anchor());
+        self.routes
+            .iter()
+            .filter(|route| route.path()[0] == start)
+            .flat_map(|route| route.vehicles.iter().filter(|v| v.stage == Stage::Waiting).map(move |v| (route.itineraire, v)))
+            .min_by(|(_, a), (_, b)| a.distance_to(anchor).total_cmp(&b.distance_to(anchor)))
+            .map_or(itineraire, |(itineraire, _)| itineraire)
+    }
+
+    /// Spawns a vehicle on the route of `itineraire`, of `class` or one picked from
+    /// the spawn mix, unless the lane it enters by is still taken at the spawn point.
+    pub fn add_vehicle<R: Rng>(&mut self, itineraire: Itineraire, class: Option<VehicleClass>, rng: &mut R) {
+        let Some(i) = self.routes.iter().position(|r| r.itineraire == itineraire) else { return };
+        let start = self.routes[i].path()[0];
+        let taken = self.shared_vehicles(i).iter().any(|v| {
+            let gap = v.distance_to(start) - (v.length - self.settings.vehicle) as f64;
+            gap <= self.settings.safety_distance
+        });
+        if taken {
+            return;
+        }
+
+        let direction = self.cross.direction();
+        match class {
+            Some(class) => self.routes[i].add_vehicle_of_class(direction, class, rng),
+            None => self.routes[i].add_vehicle(direction, rng),
+        }
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas<Window>, texture: &[Texture]) {
+        for route in self.routes.iter().rev() {
+            route.draw(canvas, texture);
         }
     }
 }
\ No newline at end of file
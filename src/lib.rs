@@ -1,28 +1,77 @@
-use lane::Stage;
 use rand::Rng;
 pub use sdl2;
 pub use sdl2::event::Event;
-pub use sdl2::keyboard::Keycode;
+pub use sdl2::keyboard::{Keycode, Mod};
 pub use sdl2::pixels::Color;
-use settings::BLOCKS;
-use std::{cell::RefCell, time::Instant};
+use std::{cell::RefCell, collections::HashSet};
+use sdl2::rect::Point;
 pub use std::{rc::Rc, time::Duration};
 
 mod settings;
-pub use settings::{Settings, Statistics, cars_texture};
+pub use settings::{Sample, Settings, Statistics, cars_texture, conflicts, BLOCK, BLOCKS};
+
+mod stats;
+pub use stats::{Aggregate, Estimate, Histogram};
+
+mod approach;
+pub use approach::{Approach, ApproachSpec, KeyPoint, APPROACHES};
+
+mod vehicle_class;
+pub use vehicle_class::{ClassSpec, VehicleClass, CAR_SPRITES, CLASSES};
+
+mod pedestrian;
+pub use pedestrian::{draw_crosswalks, Crosswalk, Pedestrian, MAX_PEDESTRIAN_WAIT};
 
 mod map;
 pub use map::draw_map;
 
 mod cars;
-pub use cars::Vehicle;
+pub use cars::{footprint, render_vehicle, Vehicle};
 
 mod lane;
-pub use lane::{Cross, Lane};
+pub use lane::{Cross, Lane, Stage};
 
 mod routes;
 pub use routes::Route;
 
+mod charts;
+
+mod json;
+pub use json::Json;
+
+mod replay;
+pub use replay::{Frame, Recorder, Replay, RouteFrame, VehicleFrame};
+
+mod simulation;
+pub use simulation::{Clock, Simulation, TICK};
+
+mod debug;
+pub use debug::{conflict_points, draw_debug_overlay};
+
+mod invariants;
+pub use invariants::check_invariants;
+
+mod experiment;
+pub use experiment::{summary_table, Experiment, Measure, Run};
+
+mod capacity;
+pub use capacity::{capacity_table, Capacity, CapacityAnalysis, Volumes};
+
+mod scheduler;
+pub use scheduler::{schedule, schedule_with_limit, Job, MAX_NODES};
+
+mod pressure;
+pub use pressure::max_pressure_phase;
+
+mod env;
+pub use env::{Action, Environment, Observation, RouteObservation};
+
+mod inspector;
+pub use inspector::{entry_area, Inspector};
+
+mod hud;
+pub use hud::{draw_hud, draw_inspector, draw_replay_hud};
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Direction {
     Up,
@@ -37,6 +86,8 @@ pub enum Itineraire {
     Left,
     Right,
     Straight,
+    /// Turns back on the inner lane: two left turns inside the intersection.
+    UTurn,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -47,48 +98,127 @@ pub enum Vilosity {
     Fast,
 }
 
-pub fn handle_keyboard_event(event: &Event, lanes: &mut Vec<Lane>, settings: Rc<Settings>) {
-    let mut binding = Lane::new(Cross::First, settings);
-    let (lane, route) = match event {
-        Event::KeyUp {
-            keycode: Some(Keycode::Up),
-            ..
-        } => (lanes.iter_mut().nth(3).unwrap(), Direction::Up),
-        Event::KeyUp {
-            keycode: Some(Keycode::Down),
-            ..
-        } => (lanes.iter_mut().nth(0).unwrap(), Direction::Down),
-        Event::KeyUp {
-            keycode: Some(Keycode::Left),
-            ..
-        } => (lanes.iter_mut().nth(2).unwrap(), Direction::Left),
-        Event::KeyUp {
-            keycode: Some(Keycode::Right),
-            ..
-        } => (lanes.iter_mut().nth(1).unwrap(), Direction::Right),
+/// The algorithms able to drive the intersection.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Controller {
+    SmartIntersection,
+    /// Orders the platoons coming to the intersection for the least total delay,
+    /// a reference for tuning the smart intersection.
+    OptimalSchedule,
+    /// Gives the intersection to the compatible routes with the longest queues
+    /// over the fewest vehicles on their exit, the baseline of adaptive signals.
+    MaxPressure,
+    /// Grants nothing itself: the routes are given the intersection by the agent
+    /// stepping an `Environment`.
+    External,
+}
+
+impl Controller {
+    /// The controllers driving the intersection on their own.
+    pub const ALL: [Controller; 3] = [Controller::SmartIntersection, Controller::OptimalSchedule, Controller::MaxPressure];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Controller::SmartIntersection => "smart intersection",
+            Controller::OptimalSchedule => "optimal schedule",
+            Controller::MaxPressure => "max pressure",
+            Controller::External => "external",
+        }
+    }
+
+    /// The controller called `name`, with dashes for the spaces on a command line.
+    pub fn from_name(name: &str) -> Option<Controller> {
+        Controller::ALL.iter().copied().find(|c| c.name() == name.replace('-', " "))
+    }
+
+    /// `time` is the simulation time in seconds, `statistic` counts what the
+    /// controller does.
+    pub fn control(&self, lanes: &mut Vec<Lane>, time: f64, statistic: &mut Statistics) {
+        match self {
+            Controller::SmartIntersection => smart_intersection(lanes, time),
+            Controller::OptimalSchedule => scheduler::optimal_schedule(lanes, time, statistic),
+            Controller::MaxPressure => pressure::max_pressure(lanes, time),
+            Controller::External => {}
+        }
+    }
+}
+
+pub fn handle_keyboard_event<R: Rng>(event: &Event, lanes: &mut [Lane], rng: &mut R) {
+    // R spawns on a random approach, E an emergency vehicle on a random approach
+    // and U a U-turn on a random approach.
+    let (approach, class, u_turn) = match event {
         Event::KeyUp {
-            keycode: Some(Keycode::R),
+            keycode: Some(keycode @ (Keycode::R | Keycode::E | Keycode::U)),
             ..
         } => {
-            let mut rng = rand::thread_rng();
-            match rng.gen_range(0, 4) {
-                0 => (lanes.iter_mut().nth(3).unwrap(), Direction::Up),
-                1 => (lanes.iter_mut().nth(0).unwrap(), Direction::Down),
-                2 => (lanes.iter_mut().nth(2).unwrap(), Direction::Left),
-                _ => (lanes.iter_mut().nth(1).unwrap(), Direction::Right),
-            }
+            let class = (*keycode == Keycode::E).then_some(VehicleClass::Emergency);
+            (Approach::ALL[rng.gen_range(0, Approach::ALL.len())], class, *keycode == Keycode::U)
         }
-        _ => (&mut binding, Direction::None),
+        Event::KeyUp {
+            keycode: Some(keycode),
+            ..
+        } => match Approach::from_key(*keycode) {
+            Some(approach) => (approach, None, false),
+            None => return,
+        },
+        _ => return,
+    };
+
+    let Some(lane) = lanes.iter_mut().find(|lane| lane.cross == approach.cross()) else { return };
+    let turn = match event {
+        Event::KeyUp { keymod, .. } => turn_from_keymod(*keymod),
+        _ => None,
     };
 
-    if route != Direction::None {
-        let mut rng = rand::thread_rng();
-        match rng.gen_range(0, 3) {
-            0 => lane.routes.iter_mut().nth(0).unwrap().add_vehicle(route),
-            1 => lane.routes.iter_mut().nth(1).unwrap().add_vehicle(route),
-            _ => lane.routes.iter_mut().nth(2).unwrap().add_vehicle(route),
+    if let Some(itineraire) = turn.or(u_turn.then_some(Itineraire::UTurn)) {
+        lane.add_vehicle(itineraire, class, rng);
+        return;
+    }
+
+    // without a modifier the turn is picked at random, U-turns are only spawned on demand.
+    let turns = [Itineraire::Left, Itineraire::Straight, Itineraire::Right];
+    let turn = turns[rng.gen_range(0, turns.len())];
+    lane.add_vehicle(turn, class, rng);
+}
+
+/// The turn forced by the modifiers held with a spawn key: Shift for Left,
+/// Alt for Straight and Ctrl for Right, none of them keeps the random pick.
+fn turn_from_keymod(keymod: Mod) -> Option<Itineraire> {
+    if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+        Some(Itineraire::Left)
+    } else if keymod.intersects(Mod::LALTMOD | Mod::RALTMOD) {
+        Some(Itineraire::Straight)
+    } else if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) {
+        Some(Itineraire::Right)
+    } else {
+        None
+    }
+}
+
+/// Counts the vehicles whose bounding boxes start to overlap on this frame.
+pub fn detect_collisions(lanes: &[Lane], statistic: &mut Statistics) {
+    let vehicles: Vec<&Vehicle> = lanes
+        .iter()
+        .flat_map(|lane| lane.routes.iter())
+        .flat_map(|route| route.vehicles.iter())
+        .collect();
+
+    let mut overlapping = HashSet::new();
+    for (i, a) in vehicles.iter().enumerate() {
+        for b in vehicles.iter().skip(i + 1) {
+            let overlap = a.footprint().iter().any(|ra| b.footprint().iter().any(|rb| ra.has_intersection(*rb)));
+            if overlap {
+                overlapping.insert((a.id.min(b.id), a.id.max(b.id)));
+            }
         }
     }
+
+    statistic.record_collisions(overlapping);
+}
+
+fn head_of_lane(lanes: &[Lane], (cross, itineraire): (Cross, Itineraire)) -> (Cross, Itineraire) {
+    let lane = lanes.iter().find(|lane| lane.cross == cross);
+    (cross, lane.map_or(itineraire, |lane| lane.head_of_lane(itineraire)))
 }
 
 fn extract_routes_mut(lanes: &mut Vec<Lane>) -> Vec<&mut Route> {
@@ -98,7 +228,8 @@ fn extract_routes_mut(lanes: &mut Vec<Lane>) -> Vec<&mut Route> {
         .collect()
 }
 
-fn chunk_routes<'a>(routes: Vec<&'a mut Route>, block: &[(Cross, Itineraire)]) -> Vec<&'a mut Route> {
+/// The routes of `block`, in the order of `routes`.
+pub fn chunk_routes<'a>(routes: Vec<&'a mut Route>, block: &[(Cross, Itineraire)]) -> Vec<&'a mut Route> {
     let mut chunks = Vec::new();
 
     for route in routes {
@@ -113,11 +244,255 @@ fn chunk_routes<'a>(routes: Vec<&'a mut Route>, block: &[(Cross, Itineraire)]) -
     chunks
 }
 
-pub fn smart_intersection(lanes: &mut Vec<Lane>) {
+/// Gives the intersection to the route of the emergency vehicle closest to its
+/// stop line: the routes crossing its path are held, and once the vehicles
+/// already inside have driven out the emergency route goes.
+fn preempt_for_emergency(lanes: &mut Vec<Lane>) {
+    let mut routes = extract_routes_mut(lanes);
+    for route in routes.iter_mut() {
+        route.interrupted &= route.preempted;
+        route.preempted = false;
+    }
+
+    let emergency = routes
+        .iter()
+        .filter_map(|route| {
+            let distance = route.vehicles
+                .iter()
+                .filter(|v| v.class == VehicleClass::Emergency && v.stage != Stage::Crossed)
+                .map(|v| v.distance_to(route.stop_anchor()) as i64)
+                .min()?;
+            Some(((route.cross, route.itineraire), distance))
+        })
+        .min_by_key(|(_, distance)| *distance);
+
+    let Some((lane, _)) = emergency else { return };
+    // behind a vehicle of the route sharing its lane the emergency vehicle gets
+    // there when that one goes.
+    let lane = head_of_lane(lanes, lane);
+    let mut routes = extract_routes_mut(lanes);
+    // an emergency vehicle never gives way to a route that waited too long.
+    if let Some(route) = routes.iter_mut().find(|r| (r.cross, r.itineraire) == lane) {
+        route.yielding = false;
+    }
+    // right turns only merge, `merge_right_turns` lets them go.
+    let Some(block) = BLOCKS.iter().find(|block| block.lane == lane) else { return };
+
+    let gap = lane.1 != Itineraire::UTurn || accepts_gap(lanes, lane);
+    let routes = extract_routes_mut(lanes);
+    let mut conflicts = chunk_routes(routes, block.intersections);
+    let mut occupied = false;
+    for route in conflicts.iter_mut().filter(|r| (r.cross, r.itineraire) != lane) {
+        route.preempted = true;
+        if route.is_entering() {
+            occupied = true;
+        } else {
+            route.interrupted |= route.stage == Stage::Crossing;
+            route.stage = Stage::Waiting;
+        }
+    }
+
+    if !occupied && gap {
+        if let Some(route) = conflicts.iter_mut().find(|r| (r.cross, r.itineraire) == lane) {
+            if route.stage != Stage::Crossing {
+                route.time = 0.0;
+                route.stage = Stage::Crossing;
+            }
+        }
+    }
+}
+
+/// Seconds the traffic of the lane a turn merges into must leave it, on top of the
+/// time the turning vehicle takes to reach the merge point.
+const MERGE_GAP: f64 = 1.5;
+
+/// Whether the vehicles heading to the merge point of the route `key` on the lane
+/// it joins leave a gap: none of them is on the merge point or about to reach it.
+/// Right turns merge where they turn, U-turns where they head back.
+fn accepts_gap(lanes: &[Lane], key: (Cross, Itineraire)) -> bool {
+    let routes = || lanes.iter().flat_map(|lane| lane.routes.iter());
+    let Some(route) = routes().find(|r| (r.cross, r.itineraire) == key) else { return true };
+    let (approach, settings) = (Approach::from(route.cross), &route.settings);
+    let merge = approach.turn_back_point(route.itineraire, settings).or(approach.turn_point(route.itineraire, settings));
+    let Some(merge) = merge else { return true };
+
+    let (dx, dy) = approach.exit(route.itineraire).spec().travel;
+    let vehicle = settings.vehicle;
+    let area = footprint(merge, Point::new(dx, dy), vehicle, vehicle, vehicle);
+
+    // from a standstill on the stop line, the head of the route needs about twice
+    // the time it would take at its turn speed.
+    let class = route.vehicles.first().map_or(VehicleClass::Car, |v| v.class);
+    let speed = class.turn_speed(vehicle).min(class.spec().speeds[3]) as f64;
+    let anchor = route.stop_anchor();
+    let reach = ((merge.x - anchor.x).abs() + (merge.y - anchor.y).abs()) as f64;
+    let gap = MERGE_GAP + 2.0 * reach / speed * TICK;
+
+    routes()
+        .filter(|r| (r.cross, r.itineraire) != key)
+        .flat_map(|r| r.vehicles.iter())
+        .all(|v| {
+            if v.footprint().iter().any(|r| r.has_intersection(area)) {
+                return false;
+            }
+
+            let travel = v.travel_direction();
+            let across = (v.position.x - merge.x) * dy.abs() + (v.position.y - merge.y) * dx.abs();
+            let ahead = (merge.x - v.position.x) * dx + (merge.y - v.position.y) * dy;
+            if (travel.x, travel.y) != (dx, dy) || across.abs() >= vehicle || ahead < 0 {
+                return true;
+            }
+            v.velocity <= 0.0 || ahead as f64 / v.velocity as f64 * TICK >= gap
+        })
+}
+
+/// Right turns cross no other movement, they merge into the lane they turn to:
+/// a right turn route is given the intersection while the traffic of that lane
+/// leaves it a gap, and is stopped on its line once its vehicles are through.
+fn merge_right_turns(lanes: &mut Vec<Lane>) {
+    let gaps: Vec<(Cross, bool)> = lanes
+        .iter()
+        .map(|lane| (lane.cross, accepts_gap(lanes, (lane.cross, Itineraire::Right))))
+        .collect();
+
+    for (cross, gap) in gaps {
+        let mut routes = extract_routes_mut(lanes);
+        let Some(route) = routes.iter_mut().find(|r| (r.cross, r.itineraire) == (cross, Itineraire::Right)) else { continue };
+        if gap && !route.is_held() && !route.vehicles.is_empty() {
+            if route.stage != Stage::Crossing {
+                route.time = 0.0;
+                route.stage = Stage::Crossing;
+            }
+        } else if !route.vehicles.iter().any(|v| v.stage == Stage::Crossing) {
+            route.stage = Stage::Waiting;
+        }
+    }
+}
+
+/// Ages the routes waiting for the intersection and guarantees them a turn: once
+/// a route waited longer than `Settings::max_wait` the routes crossing its path
+/// are held, and it goes as soon as their vehicles have driven out. The routes
+/// that waited the longest are served first.
+fn prevent_starvation(lanes: &mut Vec<Lane>, time: f64) {
+    let mut routes = extract_routes_mut(lanes);
+    for route in routes.iter_mut() {
+        route.yielding = false;
+        if route.stage == Stage::Crossing || route.vehicles.is_empty() {
+            route.waiting_since = None;
+        } else if route.waiting_since.is_none() {
+            route.waiting_since = Some(time);
+        }
+    }
+
+    let mut starving: Vec<((Cross, Itineraire), f64)> = routes
+        .iter()
+        .filter(|route| route.waited(time) > route.settings.max_wait)
+        .map(|route| ((route.cross, route.itineraire), route.waited(time)))
+        .collect();
+    starving.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    for (lane, _) in starving {
+        let lane = head_of_lane(lanes, lane);
+        // right turns only wait for a gap, there is nobody to hold for them.
+        let Some(block) = BLOCKS.iter().find(|block| block.lane == lane) else { continue };
+        let gap = lane.1 != Itineraire::UTurn || accepts_gap(lanes, lane);
+        let routes = extract_routes_mut(lanes);
+        let mut conflicts = chunk_routes(routes, block.intersections);
+        // held for a route that waited even longer.
+        if conflicts.iter().any(|r| (r.cross, r.itineraire) == lane && r.yielding) {
+            continue;
+        }
+
+        let mut occupied = false;
+        for route in conflicts.iter_mut().filter(|r| (r.cross, r.itineraire) != lane) {
+            route.yielding = true;
+            if route.is_entering() {
+                occupied = true;
+            } else {
+                route.stage = Stage::Waiting;
+            }
+        }
+
+        if let Some(route) = conflicts.iter_mut().find(|r| (r.cross, r.itineraire) == lane) {
+            if !occupied && gap && !route.is_held() {
+                route.time = 0.0;
+                route.stage = Stage::Crossing;
+            }
+        }
+    }
+}
+
+/// A route whose platoon is all in the intersection keeps it for the next platoon
+/// of its queue, unless a route crossing its path has a vehicle waiting: the
+/// routes take turns a platoon at a time.
+fn extend_platoons(lanes: &mut Vec<Lane>) {
+    let waiting: Vec<(Cross, Itineraire)> = lanes
+        .iter()
+        .flat_map(|lane| lane.routes.iter())
+        .filter(|route| route.vehicles.iter().any(|v| v.stage == Stage::Waiting))
+        .map(|route| (route.cross, route.itineraire))
+        .collect();
+
+    for route in extract_routes_mut(lanes) {
+        let lane = (route.cross, route.itineraire);
+        if route.stage != Stage::Crossing || route.is_held() || !route.is_platoon_in() {
+            continue;
+        }
+        if !waiting.iter().any(|key| conflicts(lane, *key)) {
+            route.form_platoon();
+        }
+    }
+}
+
+/// Tells each route waiting for the intersection how long the routes crossing its
+/// path still hold it, so its vehicles can time their arrival, see `Route::slot`.
+/// Right turns only merge, they aren't given one.
+fn advise_speeds(lanes: &mut Vec<Lane>) {
+    let clearing: Vec<((Cross, Itineraire), f64)> = lanes
+        .iter()
+        .flat_map(|lane| lane.routes.iter())
+        .filter(|route| route.stage == Stage::Crossing)
+        .map(|route| ((route.cross, route.itineraire), route.clearing_time()))
+        .collect();
+
+    for route in extract_routes_mut(lanes) {
+        route.slot = None;
+        let lane = (route.cross, route.itineraire);
+        if !route.settings.speed_advisory || route.stage == Stage::Crossing || route.is_held() {
+            continue;
+        }
+        let slot = clearing
+            .iter()
+            .filter(|(key, _)| conflicts(lane, *key))
+            .map(|(_, time)| *time)
+            .fold(0.0, f64::max);
+        if slot > 0.0 {
+            route.slot = Some(slot);
+        }
+    }
+}
+
+pub fn smart_intersection(lanes: &mut Vec<Lane>, time: f64) {
+    prevent_starvation(lanes, time);
+    preempt_for_emergency(lanes);
+    merge_right_turns(lanes);
+    extend_platoons(lanes);
+
     for block in BLOCKS.iter() {
+        // the left turns the U-turns merge with may be through their conflicts
+        // with the other routes and still be on the way.
+        if block.lane.1 == Itineraire::UTurn && !accepts_gap(lanes, block.lane) {
+            continue;
+        }
+
         let routes: Vec<&mut Route> = extract_routes_mut(lanes);
         let routes_chunk = Rc::new(RefCell::new(chunk_routes(routes, block.intersections)));
 
+        // a route held for an emergency vehicle or pedestrians is not given the intersection.
+        if routes_chunk.borrow().iter().any(|r| (r.cross, r.itineraire) == block.lane && r.is_held()) {
+            continue;
+        }
+
         // there is nothing to do if any of the intersection road has a vehicle.
         if routes_chunk.borrow().iter().any(|r| (r.cross, r.itineraire) == block.lane && r.vehicles.len() == 0) {
             continue;
@@ -140,7 +515,7 @@ pub fn smart_intersection(lanes: &mut Vec<Lane>) {
                 .collect::<Vec<&Vehicle>>().len()
         ) {
             if (c.cross, c.itineraire) == block.lane {
-                c.time = Instant::now();
+                c.time = 0.0;
                 c.stage = Stage::Crossing;
                 continue;
             }
@@ -153,20 +528,22 @@ pub fn smart_intersection(lanes: &mut Vec<Lane>) {
                 route.distance_to_stop_point()
             }) {
                 if (c.cross, c.itineraire) == block.lane {
-                    c.time = Instant::now();
+                    c.time = 0.0;
                     c.stage = Stage::Crossing;
                     continue;
                 }
         } else if let Some(c) = b.iter_mut()
             .max_by_key(|r| r.vehicles.len()) {
             if (c.cross, c.itineraire) == block.lane {
-                c.time = Instant::now();
+                c.time = 0.0;
                 c.stage = Stage::Crossing;
                 continue;
             }
         }
     
     }
+
+    advise_speeds(lanes);
 }
 
 
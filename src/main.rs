@@ -1,9 +1,10 @@
-use std::cell::RefCell;
+use std::time::Instant;
 
 use sdl2::{
     image::LoadTexture,
     rect::{Point, Rect},
     render::{Texture, WindowCanvas},
+    ttf::Font,
 };
 use smart_road::*;
 
@@ -23,9 +24,76 @@ fn render(
     Ok(())
 }
 
+fn draw_background(canvas: &mut WindowCanvas, texture: &Texture, positions_and_sprite: &[(Point, Rect)], settings: Rc<Settings>) {
+    canvas.set_draw_color(Color::RGB(80, 80, 80));
+    canvas.clear();
+    for (position, sprite) in positions_and_sprite.iter() {
+        render(canvas, texture, *position, *sprite).unwrap();
+    }
+
+    // map
+    draw_map(canvas, settings);
+}
+
+/// Plays a trajectory log recorded with `--record` back through the renderer.
+fn run_replay(
+    mut replay: Replay,
+    canvas: &mut WindowCanvas,
+    event_pump: &mut sdl2::EventPump,
+    draw_background: &dyn Fn(&mut WindowCanvas),
+    cars: &[Texture],
+    font: &Font,
+) {
+    let mut last = Instant::now();
+    'replay: loop {
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. }
+                | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => break 'replay,
+                Event::KeyDown { keycode: Some(Keycode::Space), .. } => replay.paused = !replay.paused,
+                Event::KeyDown { keycode: Some(Keycode::Right), .. } => replay.seek(5.0),
+                Event::KeyDown { keycode: Some(Keycode::Left), .. } => replay.seek(-5.0),
+                Event::KeyDown { keycode: Some(Keycode::Up), .. } => replay.faster(),
+                Event::KeyDown { keycode: Some(Keycode::Down), .. } => replay.slower(),
+                Event::KeyDown { keycode: Some(Keycode::Period), .. } => {
+                    replay.paused = true;
+                    replay.step(1);
+                }
+                Event::KeyDown { keycode: Some(Keycode::Comma), .. } => {
+                    replay.paused = true;
+                    replay.step(-1);
+                }
+                _ => {}
+            }
+        }
+
+        let now = Instant::now();
+        replay.update(now.duration_since(last).as_secs_f64());
+        last = now;
+
+        draw_background(canvas);
+        for v in replay.frame().vehicles.iter() {
+            let texture = &cars[v.texture % cars.len()];
+            render_vehicle(canvas, texture, v.bounds, v.heading).unwrap();
+        }
+        draw_replay_hud(canvas, font, &replay).unwrap();
+
+        canvas.present();
+        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
+    }
+}
+
 pub fn main() {
-    let settings = Rc::new(Settings::new(1000, 1000, 30, 1, 100.0));
-    let mut statistic: Statistics = Statistics::new();
+    // `--replay <file>` plays a log back, `--record <file>` writes one while simulating,
+    // `--controller optimal-schedule` picks the algorithm driving the intersection
+    // and `--check-invariants` checks the safety invariants in release builds too,
+    // the other options are the ones of `Settings::apply_args`.
+    let args: Vec<String> = std::env::args().collect();
+    let option = |name: &str| args.iter().position(|a| a == name).and_then(|i| args.get(i + 1)).cloned();
+
+    let mut settings = Settings::new(1000, 1000, 30, 1, 100.0);
+    settings.apply_args(&args).unwrap_or_else(|e| panic!("cannot use {}", e));
+    let settings = Rc::new(settings);
 
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
@@ -38,13 +106,6 @@ pub fn main() {
 
     let mut canvas = window.into_canvas().build().unwrap();
 
-    let lanes: Rc<RefCell<Vec<Lane>>> = Rc::new(RefCell::new(vec![
-        Lane::new(Cross::First, settings.clone()),
-        Lane::new(Cross::Second, settings.clone()),
-        Lane::new(Cross::Third, settings.clone()),
-        Lane::new(Cross::Fourth, settings.clone()),
-    ]));
-
     let texture_creator = canvas.texture_creator();
     let texture = texture_creator.load_texture("assets/marche.png").unwrap();
     let (width, height, half_width, half_height) = (
@@ -61,14 +122,33 @@ pub fn main() {
         (Point::new(half_width - width / 2, half_height - height / 2) , Rect::new(width, height, width as u32, height as u32)),
     ];
 
-    let a: Vec<Texture> = cars_texture(&texture_creator);
-    canvas.present();
+    let a: Vec<Texture> = cars_texture(&texture_creator).unwrap_or_else(|e| panic!("cannot load the {}", e));
+
+    let ttf_context = sdl2::ttf::init().unwrap();
+    let mut font = ttf_context.load_font("assets/Roboto-Thin.ttf", 14).unwrap();
+    font.set_style(sdl2::ttf::FontStyle::BOLD);
+
     let mut event_pump: sdl2::EventPump = sdl_context.event_pump().unwrap();
-    let mut i = 0;
+    let background = |canvas: &mut WindowCanvas| draw_background(canvas, &texture, &positions_and_sprite, settings.clone());
+
+    if let Some(path) = option("--replay") {
+        let replay = Replay::load(&path).unwrap_or_else(|e| panic!("cannot replay {}", e));
+        run_replay(replay, &mut canvas, &mut event_pump, &background, &a, &font);
+        return;
+    }
+    let mut recorder = option("--record").map(|path| Recorder::create(&path).unwrap_or_else(|e| panic!("cannot record {}", e)));
+
+    let controller = option("--controller").map_or(Controller::SmartIntersection, |name| {
+        Controller::from_name(&name).unwrap_or_else(|| panic!("cannot use the controller {}", name))
+    });
+    let mut simulation = Simulation::new(settings.clone(), controller);
+    simulation.check_invariants |= args.iter().any(|a| a == "--check-invariants");
+    let mut clock = Clock::new();
+    let mut show_debug = false;
+    let mut inspector = Inspector::new();
+    let mut last = Instant::now();
+    canvas.present();
     'running: loop {
-        i = (i + 1) % 255;
-        canvas.set_draw_color(Color::RGB(80, 80, 80));
-        canvas.clear();
         for event in event_pump.poll_iter() {
             match event {
                 Event::Quit { .. }
@@ -78,33 +158,48 @@ pub fn main() {
                 } => {
                     break 'running;
                 }
+                // time controls: pause, one tick at a time and time scale.
+                Event::KeyDown { keycode: Some(Keycode::Space), .. } => clock.toggle_pause(),
+                Event::KeyDown { keycode: Some(Keycode::N), .. } => clock.step(),
+                Event::KeyDown { keycode: Some(Keycode::Equals | Keycode::KpPlus), .. } => clock.faster(),
+                Event::KeyDown { keycode: Some(Keycode::Minus | Keycode::KpMinus), .. } => clock.slower(),
+                Event::KeyDown { keycode: Some(Keycode::D), .. } => show_debug = !show_debug,
+                Event::KeyDown { keycode: Some(Keycode::P), .. } => simulation.add_pedestrian(),
                 _ => {
-                    handle_keyboard_event(&event, &mut lanes.borrow_mut(), settings.clone());
+                    if !inspector.handle_event(&event, &mut simulation) {
+                        handle_keyboard_event(&event, &mut simulation.lanes, &mut simulation.rng);
+                    }
                 }
             }
         }
 
-        canvas.clear();
-        for (position, sprite) in positions_and_sprite.iter() {
-            render(&mut canvas, &texture, *position, *sprite).unwrap();
-        }
-
-        // map
-        draw_map(&mut canvas, settings.clone());
-
-        {
-            let mut lanes_borrowed = lanes.borrow_mut();
-            for lane in lanes_borrowed.iter_mut() {
-                lane.update(&mut canvas, &a, &mut statistic);
+        let now = Instant::now();
+        for _ in 0..clock.ticks(now.duration_since(last).as_secs_f64()) {
+            simulation.tick();
+            if let Some(recorder) = recorder.as_mut() {
+                recorder.record(&simulation.lanes, simulation.time).unwrap();
             }
         }
+        last = now;
 
-        // the smart road algorithm to avoid collisions
-        smart_intersection(&mut lanes.borrow_mut());
+        background(&mut canvas);
+        draw_crosswalks(&mut canvas, &simulation.crosswalks).unwrap();
+        for lane in simulation.lanes.iter() {
+            lane.draw(&mut canvas, &a);
+        }
+        if show_debug {
+            draw_debug_overlay(&mut canvas, &simulation).unwrap();
+        }
+        draw_hud(&mut canvas, &font, &simulation, &clock).unwrap();
+        draw_inspector(&mut canvas, &font, &simulation, &inspector).unwrap();
 
         canvas.present();
         ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
     }
 
-    statistic.display_statistics_window(&mut event_pump);
+    if let Some(recorder) = recorder.as_mut() {
+        recorder.flush().unwrap();
+    }
+
+    simulation.statistic.display_statistics_window(&mut event_pump);
 }
@@ -0,0 +1,189 @@
+use rand::Rng;
+use sdl2::{
+    pixels::Color,
+    rect::{Point, Rect},
+    render::{BlendMode, WindowCanvas},
+};
+
+use crate::{lane::Stage, Approach, Lane, Settings, Statistics, Vehicle};
+
+/// Walking speed in pixels per second.
+const WALKING_SPEED: f64 = 60.0;
+/// Pedestrians still step on the crosswalk during the first seconds of a walk phase.
+const WALK_START_WINDOW: f64 = 4.0;
+/// Longest wait, in seconds, before the pedestrians stop waiting for a gap in the
+/// traffic and get a phase of their own.
+pub const MAX_PEDESTRIAN_WAIT: f64 = 20.0;
+/// Distance from the road the pedestrians wait at.
+const CURB: i32 = 8;
+
+#[derive(Debug, Clone)]
+pub struct Pedestrian {
+    /// Simulation time the pedestrian pressed the button at.
+    pub arrived: f64,
+    /// Distance walked on the crosswalk, `None` while waiting on the curb.
+    pub walked: Option<f64>,
+    /// Crossing from the low coordinates side of the road to the high one.
+    pub forward: bool,
+}
+
+/// A crosswalk over the road of one approach and the pedestrians using it.
+#[derive(Debug, Clone)]
+pub struct Crosswalk {
+    pub approach: Approach,
+    pub area: Rect,
+    pub pedestrians: Vec<Pedestrian>,
+    /// Simulation time the walk phase started at, `None` while vehicles have the crosswalk.
+    pub walk_since: Option<f64>,
+}
+
+impl Crosswalk {
+    pub fn new(approach: Approach, settings: &Settings) -> Crosswalk {
+        Crosswalk {
+            approach,
+            area: approach.crosswalk(settings),
+            pedestrians: Vec::new(),
+            walk_since: None,
+        }
+    }
+
+    fn is_across_x(&self) -> bool {
+        self.area.width() > self.area.height()
+    }
+
+    /// Distance from one curb to the other.
+    fn length(&self) -> f64 {
+        (self.area.width().max(self.area.height()) as i32 + 2 * CURB) as f64
+    }
+
+    pub fn is_in_use(&self) -> bool {
+        self.walk_since.is_some()
+    }
+
+    pub fn waiting(&self) -> usize {
+        self.pedestrians.iter().filter(|p| p.walked.is_none()).count()
+    }
+
+    /// A pedestrian arriving at `time`, `rng` draws the way they walk.
+    pub fn add_pedestrian<R: Rng>(&mut self, time: f64, rng: &mut R) {
+        self.pedestrians.push(Pedestrian {
+            arrived: time,
+            walked: None,
+            forward: rng.gen(),
+        });
+    }
+
+    /// Where a pedestrian stands: on the curb while waiting, on the crosswalk while walking.
+    pub fn position(&self, pedestrian: &Pedestrian) -> Point {
+        let walked = pedestrian.walked.unwrap_or(0.0) as i32;
+        let center = self.area.center();
+        let (low, high) = if self.is_across_x() {
+            (self.area.left() - CURB, self.area.right() + CURB)
+        } else {
+            (self.area.top() - CURB, self.area.bottom() + CURB)
+        };
+        // each way keeps to its own half of the crosswalk.
+        let (across, side) = if pedestrian.forward { (low + walked, -1) } else { (high - walked, 1) };
+        let lane = side * self.area.width().min(self.area.height()) as i32 / 4;
+
+        if self.is_across_x() {
+            Point::new(across, center.y + lane)
+        } else {
+            Point::new(center.x + lane, across)
+        }
+    }
+
+    /// Starts a walk phase once the crosswalk is clear and either the traffic leaves a
+    /// gap or the pedestrians have waited too long, ends it when everybody crossed.
+    pub fn control(&mut self, lanes: &[Lane], settings: &Settings, time: f64) {
+        let walking = self.pedestrians.iter().any(|p| p.walked.is_some());
+        if let Some(since) = self.walk_since {
+            if !walking && (self.waiting() == 0 || time - since > WALK_START_WINDOW) {
+                self.walk_since = None;
+            }
+            return;
+        }
+
+        let Some(oldest) = self.pedestrians.iter().filter(|p| p.walked.is_none()).map(|p| p.arrived).reduce(f64::min) else { return };
+        let grow = |d: i32| Rect::new(
+            self.area.x() - d,
+            self.area.y() - d,
+            self.area.width() + 2 * d as u32,
+            self.area.height() + 2 * d as u32,
+        );
+
+        let vehicles = || lanes.iter().flat_map(|lane| lane.routes.iter()).flat_map(|route| route.vehicles.iter());
+        // the vehicles leaving by this road must not get caught in the intersection.
+        let mut leaving = lanes
+            .iter()
+            .flat_map(|lane| lane.routes.iter())
+            .filter(|route| Approach::from(route.cross).exit_leg(route.itineraire) == self.approach)
+            .flat_map(|route| route.vehicles.iter());
+        let near = grow(2 * settings.vehicle);
+        let on = |v: &Vehicle, area: Rect| v.footprint().iter().any(|r| r.has_intersection(area));
+
+        let clear = !vehicles().any(|v| on(v, self.area))
+            && !leaving.any(|v| v.stage == Stage::Crossing || on(v, near));
+        let around = grow(settings.safety_distance as i32);
+        let gap = !vehicles().any(|v| v.velocity > v.velosity_type[1] && on(v, around));
+
+        if clear && (gap || time - oldest > MAX_PEDESTRIAN_WAIT) {
+            self.walk_since = Some(time);
+        }
+    }
+
+    /// Moves the pedestrians and records the wait of the ones stepping on the crosswalk.
+    pub fn update(&mut self, statistic: &mut Statistics, time: f64, dt: f64) {
+        let may_start = matches!(self.walk_since, Some(since) if time - since <= WALK_START_WINDOW);
+        let length = self.length();
+
+        for pedestrian in self.pedestrians.iter_mut() {
+            match pedestrian.walked {
+                Some(walked) => pedestrian.walked = Some(walked + WALKING_SPEED * dt),
+                None if may_start => {
+                    statistic.pedestrian_wait.push(time - pedestrian.arrived);
+                    pedestrian.walked = Some(0.0);
+                }
+                None => {}
+            }
+        }
+
+        let before = self.pedestrians.len();
+        self.pedestrians.retain(|p| p.walked.is_none_or(|walked| walked < length));
+        statistic.pedestrians_crossed += before - self.pedestrians.len();
+    }
+}
+
+/// Draws the zebra stripes, green during a walk phase, and the pedestrians.
+pub fn draw_crosswalks(canvas: &mut WindowCanvas, crosswalks: &[Crosswalk]) -> Result<(), String> {
+    const STRIPE: i32 = 6;
+    canvas.set_blend_mode(BlendMode::Blend);
+
+    for crosswalk in crosswalks {
+        let area = crosswalk.area;
+        canvas.set_draw_color(match crosswalk.is_in_use() {
+            true => Color::RGBA(120, 230, 120, 160),
+            false => Color::RGBA(255, 255, 255, 110),
+        });
+        if crosswalk.is_across_x() {
+            for x in (area.left()..area.right()).step_by(2 * STRIPE as usize) {
+                canvas.fill_rect(Rect::new(x, area.top(), STRIPE as u32, area.height()))?;
+            }
+        } else {
+            for y in (area.top()..area.bottom()).step_by(2 * STRIPE as usize) {
+                canvas.fill_rect(Rect::new(area.left(), y, area.width(), STRIPE as u32))?;
+            }
+        }
+
+        for pedestrian in crosswalk.pedestrians.iter() {
+            canvas.set_draw_color(match pedestrian.walked {
+                Some(_) => Color::RGB(255, 255, 255),
+                None => Color::RGB(255, 180, 40),
+            });
+            canvas.fill_rect(Rect::from_center(crosswalk.position(pedestrian), 8, 8))?;
+        }
+    }
+
+    canvas.set_blend_mode(BlendMode::None);
+    Ok(())
+}
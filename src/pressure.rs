@@ -0,0 +1,78 @@
+use crate::{
+    accepts_gap, conflicts, extract_routes_mut, head_of_lane, lane::Stage, merge_right_turns, preempt_for_emergency,
+    prevent_starvation, Approach, Cross, Itineraire, Lane,
+};
+
+/// The pressure of a route: its vehicles before the stop line less the vehicles
+/// already through on the leg it leaves by.
+fn pressure(lanes: &[Lane], (cross, itineraire): (Cross, Itineraire)) -> f64 {
+    let routes = || lanes.iter().flat_map(|lane| lane.routes.iter());
+    let Some(route) = routes().find(|r| (r.cross, r.itineraire) == (cross, itineraire)) else { return 0.0 };
+    let upstream = route.vehicles.iter().filter(|v| v.stage == Stage::Waiting).count();
+    let leg = Approach::from(cross).exit_leg(itineraire);
+    let downstream = routes()
+        .filter(|r| Approach::from(r.cross).exit_leg(r.itineraire) == leg)
+        .flat_map(|r| r.vehicles.iter())
+        .filter(|v| v.stage == Stage::Crossed)
+        .count();
+    upstream as f64 - downstream as f64
+}
+
+/// The routes whose paths don't cross with the largest sum of pressures, among
+/// `pressures`. Only the routes of positive pressure are worth a grant.
+pub fn max_pressure_phase(pressures: &[((Cross, Itineraire), f64)]) -> Vec<(Cross, Itineraire)> {
+    fn best(candidates: &[((Cross, Itineraire), f64)], phase: &mut Vec<(Cross, Itineraire)>, sum: f64, found: &mut (f64, Vec<(Cross, Itineraire)>)) {
+        let Some(((lane, pressure), rest)) = candidates.split_first() else {
+            if sum > found.0 {
+                *found = (sum, phase.clone());
+            }
+            return;
+        };
+        // even all the routes left can't beat the best phase found.
+        if sum + candidates.iter().map(|(_, p)| p).sum::<f64>() <= found.0 {
+            return;
+        }
+        if !phase.iter().any(|other| conflicts(*lane, *other)) {
+            phase.push(*lane);
+            best(rest, phase, sum + pressure, found);
+            phase.pop();
+        }
+        best(rest, phase, sum, found);
+    }
+
+    let candidates: Vec<((Cross, Itineraire), f64)> = pressures.iter().copied().filter(|(_, p)| *p > 0.0).collect();
+    let mut found = (0.0, Vec::new());
+    best(&candidates, &mut Vec::new(), 0.0, &mut found);
+    found.1
+}
+
+/// Max pressure: each time the routes given the intersection have driven through
+/// it, gives it to the phase of compatible routes with the largest pressure, see
+/// `max_pressure_phase`. Right turns only merge, `merge_right_turns` lets them go.
+pub fn max_pressure(lanes: &mut Vec<Lane>, time: f64) {
+    prevent_starvation(lanes, time);
+    preempt_for_emergency(lanes);
+    merge_right_turns(lanes);
+
+    let routes = || lanes.iter().flat_map(|lane| lane.routes.iter()).filter(|r| r.itineraire != Itineraire::Right);
+    if routes().any(|route| route.stage == Stage::Crossing) {
+        return;
+    }
+
+    // a route behind a vehicle of the one sharing its lane can't move, nor can a
+    // held one or a U-turn without a gap to merge in.
+    let pressures: Vec<((Cross, Itineraire), f64)> = routes()
+        .map(|route| (route.cross, route.itineraire))
+        .filter(|lane| head_of_lane(lanes, *lane) == *lane && (lane.1 != Itineraire::UTurn || accepts_gap(lanes, *lane)))
+        .filter(|lane| routes().any(|route| (route.cross, route.itineraire) == *lane && !route.is_held()))
+        .map(|lane| (lane, pressure(lanes, lane)))
+        .collect();
+    let phase = max_pressure_phase(&pressures);
+
+    for route in extract_routes_mut(lanes) {
+        if phase.contains(&(route.cross, route.itineraire)) {
+            route.time = 0.0;
+            route.stage = Stage::Crossing;
+        }
+    }
+}
@@ -0,0 +1,322 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+};
+
+use sdl2::rect::Rect;
+
+use crate::{json::Json, lane::Stage, Cross, Direction, Itineraire, Lane, VehicleClass};
+
+/// State of a route at one tick.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteFrame {
+    pub cross: Cross,
+    pub itineraire: Itineraire,
+    pub stage: Stage,
+}
+
+/// State of a vehicle at one tick.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VehicleFrame {
+    pub id: usize,
+    pub x: i32,
+    pub y: i32,
+    pub heading: f64,
+    pub speed: f64,
+    pub stage: Stage,
+    pub route: Direction,
+    pub itineraire: Itineraire,
+    pub texture: usize,
+    pub class: VehicleClass,
+    /// The box the vehicle takes on the road, its sprite is stretched over it.
+    pub bounds: Rect,
+}
+
+/// One line of a trajectory log: everything needed to draw a tick again.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub time: f64,
+    pub routes: Vec<RouteFrame>,
+    pub vehicles: Vec<VehicleFrame>,
+}
+
+pub(crate) fn cross_from_str(s: &str) -> Result<Cross, String> {
+    match s {
+        "First" => Ok(Cross::First),
+        "Second" => Ok(Cross::Second),
+        "Third" => Ok(Cross::Third),
+        "Fourth" => Ok(Cross::Fourth),
+        _ => Err(format!("unknown cross `{}`", s)),
+    }
+}
+
+pub(crate) fn itineraire_from_str(s: &str) -> Result<Itineraire, String> {
+    match s {
+        "Left" => Ok(Itineraire::Left),
+        "Right" => Ok(Itineraire::Right),
+        "Straight" => Ok(Itineraire::Straight),
+        "UTurn" => Ok(Itineraire::UTurn),
+        _ => Err(format!("unknown itineraire `{}`", s)),
+    }
+}
+
+fn stage_from_str(s: &str) -> Result<Stage, String> {
+    match s {
+        "Crossing" => Ok(Stage::Crossing),
+        "Crossed" => Ok(Stage::Crossed),
+        "Waiting" => Ok(Stage::Waiting),
+        _ => Err(format!("unknown stage `{}`", s)),
+    }
+}
+
+fn class_from_str(s: &str) -> Result<VehicleClass, String> {
+    match s {
+        "Car" => Ok(VehicleClass::Car),
+        "Bus" => Ok(VehicleClass::Bus),
+        "Truck" => Ok(VehicleClass::Truck),
+        "Motorbike" => Ok(VehicleClass::Motorbike),
+        "Emergency" => Ok(VehicleClass::Emergency),
+        _ => Err(format!("unknown vehicle class `{}`", s)),
+    }
+}
+
+fn direction_from_str(s: &str) -> Result<Direction, String> {
+    match s {
+        "Up" => Ok(Direction::Up),
+        "Down" => Ok(Direction::Down),
+        "Left" => Ok(Direction::Left),
+        "Right" => Ok(Direction::Right),
+        "None" => Ok(Direction::None),
+        _ => Err(format!("unknown direction `{}`", s)),
+    }
+}
+
+pub(crate) fn text<T: std::fmt::Debug>(value: T) -> Json {
+    Json::String(format!("{:?}", value))
+}
+
+fn number(json: &Json, key: &str) -> Result<f64, String> {
+    json.field(key)?.as_f64().ok_or_else(|| format!("`{}` is not a number", key))
+}
+
+fn string<'a>(json: &'a Json, key: &str) -> Result<&'a str, String> {
+    json.field(key)?.as_str().ok_or_else(|| format!("`{}` is not a string", key))
+}
+
+fn bounds(json: &Json) -> Result<Rect, String> {
+    let values = json.field("bounds")?
+        .as_array()
+        .ok_or("`bounds` is not an array")?
+        .iter()
+        .map(|n| n.as_f64().ok_or("`bounds` is not a list of numbers"))
+        .collect::<Result<Vec<f64>, &str>>()?;
+
+    match values[..] {
+        [x, y, w, h] => Ok(Rect::new(x as i32, y as i32, w as u32, h as u32)),
+        _ => Err(String::from("`bounds` is not [x, y, width, height]")),
+    }
+}
+
+impl Frame {
+    pub fn capture(lanes: &[Lane], time: f64) -> Frame {
+        let routes = lanes.iter().flat_map(|lane| lane.routes.iter());
+
+        Frame {
+            time,
+            routes: routes
+                .clone()
+                .map(|r| RouteFrame {
+                    cross: r.cross,
+                    itineraire: r.itineraire,
+                    stage: r.stage,
+                })
+                .collect(),
+            vehicles: routes
+                .flat_map(|r| r.vehicles.iter())
+                .map(|v| VehicleFrame {
+                    id: v.id,
+                    x: v.position.x,
+                    y: v.position.y,
+                    heading: v.heading(),
+                    speed: v.velocity as f64,
+                    stage: v.stage,
+                    route: v.route,
+                    itineraire: v.itineraire,
+                    texture: v.texture_index(),
+                    class: v.class,
+                    bounds: v.bounding_box(),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn to_json(&self) -> Json {
+        let routes = self.routes
+            .iter()
+            .map(|r| Json::Object(vec![
+                ("cross".into(), text(r.cross)),
+                ("itineraire".into(), text(r.itineraire)),
+                ("stage".into(), text(r.stage)),
+            ]))
+            .collect();
+
+        let vehicles = self.vehicles
+            .iter()
+            .map(|v| Json::Object(vec![
+                ("id".into(), Json::Number(v.id as f64)),
+                ("x".into(), Json::Number(v.x as f64)),
+                ("y".into(), Json::Number(v.y as f64)),
+                ("heading".into(), Json::Number(v.heading)),
+                ("speed".into(), Json::Number(v.speed)),
+                ("stage".into(), text(v.stage)),
+                ("route".into(), text(v.route)),
+                ("itineraire".into(), text(v.itineraire)),
+                ("texture".into(), Json::Number(v.texture as f64)),
+                ("class".into(), text(v.class)),
+                ("bounds".into(), Json::Array(vec![
+                    Json::Number(v.bounds.x() as f64),
+                    Json::Number(v.bounds.y() as f64),
+                    Json::Number(v.bounds.width() as f64),
+                    Json::Number(v.bounds.height() as f64),
+                ])),
+            ]))
+            .collect();
+
+        Json::Object(vec![
+            ("t".into(), Json::Number(self.time)),
+            ("routes".into(), Json::Array(routes)),
+            ("vehicles".into(), Json::Array(vehicles)),
+        ])
+    }
+
+    pub fn from_json(json: &Json) -> Result<Frame, String> {
+        let list = |key: &str| json.field(key)?.as_array().ok_or_else(|| format!("`{}` is not an array", key));
+
+        let routes = list("routes")?
+            .iter()
+            .map(|r| Ok(RouteFrame {
+                cross: cross_from_str(string(r, "cross")?)?,
+                itineraire: itineraire_from_str(string(r, "itineraire")?)?,
+                stage: stage_from_str(string(r, "stage")?)?,
+            }))
+            .collect::<Result<Vec<_>, String>>()?;
+
+        let vehicles = list("vehicles")?
+            .iter()
+            .map(|v| Ok(VehicleFrame {
+                id: number(v, "id")? as usize,
+                x: number(v, "x")? as i32,
+                y: number(v, "y")? as i32,
+                heading: number(v, "heading")?,
+                speed: number(v, "speed")?,
+                stage: stage_from_str(string(v, "stage")?)?,
+                route: direction_from_str(string(v, "route")?)?,
+                itineraire: itineraire_from_str(string(v, "itineraire")?)?,
+                texture: number(v, "texture")? as usize,
+                class: class_from_str(string(v, "class")?)?,
+                bounds: bounds(v)?,
+            }))
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(Frame {
+            time: number(json, "t")?,
+            routes,
+            vehicles,
+        })
+    }
+}
+
+/// Writes one `Frame` per tick to a newline-delimited JSON file.
+pub struct Recorder {
+    writer: BufWriter<File>,
+}
+
+impl Recorder {
+    pub fn create(path: &str) -> Result<Recorder, String> {
+        let file = File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+        Ok(Recorder { writer: BufWriter::new(file) })
+    }
+
+    pub fn record(&mut self, lanes: &[Lane], time: f64) -> Result<(), String> {
+        writeln!(self.writer, "{}", Frame::capture(lanes, time).to_json()).map_err(|e| e.to_string())
+    }
+
+    pub fn flush(&mut self) -> Result<(), String> {
+        self.writer.flush().map_err(|e| e.to_string())
+    }
+}
+
+/// Plays a trajectory log back with pause, seek and speed control.
+pub struct Replay {
+    pub frames: Vec<Frame>,
+    pub time: f64,
+    pub speed: f64,
+    pub paused: bool,
+    cursor: usize,
+}
+
+impl Replay {
+    pub fn load(path: &str) -> Result<Replay, String> {
+        let file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+        let mut frames = Vec::new();
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|e| e.to_string())?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let json = Json::parse(&line).map_err(|e| format!("{}:{}: {}", path, i + 1, e))?;
+            frames.push(Frame::from_json(&json).map_err(|e| format!("{}:{}: {}", path, i + 1, e))?);
+        }
+
+        if frames.is_empty() {
+            return Err(format!("{}: no frame recorded", path));
+        }
+
+        Ok(Replay {
+            time: frames[0].time,
+            frames,
+            speed: 1.0,
+            paused: false,
+            cursor: 0,
+        })
+    }
+
+    pub fn frame(&self) -> &Frame {
+        &self.frames[self.cursor]
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.frames[self.frames.len() - 1].time
+    }
+
+    /// Advances the playback by `dt` seconds of wall clock.
+    pub fn update(&mut self, dt: f64) {
+        if !self.paused {
+            self.seek(dt * self.speed);
+        }
+    }
+
+    /// Moves the playback by `delta` seconds of recorded time, clamped to the log.
+    pub fn seek(&mut self, delta: f64) {
+        self.time = (self.time + delta).max(self.frames[0].time).min(self.duration());
+        self.cursor = match self.frames.binary_search_by(|f| f.time.partial_cmp(&self.time).unwrap()) {
+            Ok(i) => i,
+            Err(i) => i.saturating_sub(1),
+        };
+    }
+
+    /// Moves by whole frames, used to review an incident while paused.
+    pub fn step(&mut self, frames: isize) {
+        let last = self.frames.len() as isize - 1;
+        self.cursor = (self.cursor as isize + frames).max(0).min(last) as usize;
+        self.time = self.frames[self.cursor].time;
+    }
+
+    pub fn faster(&mut self) {
+        self.speed = (self.speed * 2.0).min(16.0);
+    }
+
+    pub fn slower(&mut self) {
+        self.speed = (self.speed / 2.0).max(0.125);
+    }
+}
@@ -1,9 +1,65 @@
-use std::{rc::Rc, time::{Duration, Instant}};
+use std::rc::Rc;
 
 use rand::Rng;
-use sdl2::{rect::Point, render::{Canvas, Texture}, video::Window};
+use sdl2::{rect::{Point, Rect}, render::{Canvas, Texture}, video::Window};
 
-use crate::{lane::Stage, Cross, Direction, Itineraire, Settings, Statistics, Vehicle, Vilosity};
+use crate::{lane::Stage, Approach, Cross, Direction, Itineraire, Settings, Statistics, Vehicle, VehicleClass, Vilosity, CLASSES, TICK};
+
+/// Slows a vehicle down close to its stop line and holds it on the line instead of
+/// crawling into the intersection: long vehicles keep the intersection busy long
+/// enough for a crawl to reach the other routes.
+fn hold(vehicle: &mut Vehicle, distance_to_stop: f64) {
+    vehicle.set_vilosity(Vilosity::Reduce);
+    if vehicle.stage == Stage::Waiting && distance_to_stop < 1.0 {
+        vehicle.velocity = 0.0;
+    }
+}
+
+/// How close to the stop line a waiting vehicle is held: `safety_distance`, or
+/// when its route has a slot coming the distance it drives in a tick, the speed
+/// advisory already has it reach the line as the slot opens.
+fn hold_distance(vehicle: &Vehicle, safety_distance: f64, slot: Option<f64>) -> f64 {
+    match slot {
+        Some(_) => vehicle.velocity.ceil() as f64 + 1.0,
+        None => safety_distance,
+    }
+}
+
+/// Distance from `position` to the stop line at `stop_point`, whose x is zero
+/// on the vertical approaches.
+fn to_stop_line(stop_point: Point, position: Point) -> f64 {
+    if stop_point.x == 0 {
+        (stop_point.y - position.y).abs().into()
+    } else {
+        (stop_point.x - position.x).abs().into()
+    }
+}
+
+/// Whether `other` drives in front of `vehicle` on the lane it is on, a long
+/// vehicle that has just turned away still has its back on it.
+fn is_ahead_in_lane(vehicle: &Vehicle, other: &Vehicle) -> bool {
+    let travel = vehicle.travel_direction();
+    let on_lane = if other.travel_direction() == travel {
+        Some(other.position)
+    } else {
+        other.last_turn().filter(|(_, before)| *before == travel && other.footprint().len() > 1).map(|(turn, _)| turn)
+    };
+    let Some(on_lane) = on_lane else { return false };
+
+    let d = on_lane - vehicle.position;
+    let across = d.x * travel.y - d.y * travel.x;
+    d.x * travel.x + d.y * travel.y > 0 && across.abs() < vehicle.width.max(other.width)
+}
+
+/// The vehicle `vehicle` follows: the last of `ahead`, the vehicles before it on
+/// its route, or the closest of `others` ahead of it on its lane.
+pub(crate) fn leader<'a>(vehicle: &Vehicle, ahead: &'a [Vehicle], others: &[&'a Vehicle]) -> Option<&'a Vehicle> {
+    // the U-turns ahead that already head back are no longer in the way.
+    let own = ahead.last().filter(|leader| leader.travel_direction() + vehicle.travel_direction() != Point::new(0, 0));
+    own.into_iter()
+        .chain(others.iter().copied().filter(|other| is_ahead_in_lane(vehicle, other)))
+        .min_by(|a, b| vehicle.distance(a).total_cmp(&vehicle.distance(b)))
+}
 
 #[derive(Debug, Clone)]
 pub struct Route {
@@ -13,11 +69,27 @@ pub struct Route {
     pub stop_point: Point,
     pub settings: Rc<Settings>,
     pub stage: Stage,
-    pub waiting_since: Option<Instant>,
+    pub waiting_since: Option<f64>,
     pub is_vehicle_in_intersection: bool,
     detected_collisons: bool,
     pub other_route_crossed: bool,
-    pub time: Instant,
+    /// Held by the controller to give way to an emergency vehicle.
+    pub preempted: bool,
+    /// The pre-emption stopped the route while it had the intersection, and holds
+    /// it still.
+    pub interrupted: bool,
+    /// Pedestrians are crossing the road the route leaves by.
+    pub exit_blocked: bool,
+    /// Held by the controller for a conflicting route that waited too long.
+    pub yielding: bool,
+    /// Ids of the vehicles let in with the current grant of the intersection, see
+    /// `Route::form_platoon`.
+    pub platoon: Vec<usize>,
+    /// Seconds until the routes crossing the path of this one are out of the way,
+    /// set by the controller while they hold the intersection.
+    pub slot: Option<f64>,
+    /// Simulation time, in seconds, since the route was last given the intersection.
+    pub time: f64,
 }
 
 impl Route {
@@ -38,7 +110,13 @@ impl Route {
             waiting_since: None,
             detected_collisons: false,
             other_route_crossed: false,
-            time: Instant::now(),
+            preempted: false,
+            interrupted: false,
+            exit_blocked: false,
+            yielding: false,
+            platoon: Vec::new(),
+            slot: None,
+            time: 0.0,
         }
     }
 
@@ -53,17 +131,24 @@ impl Route {
             return;
         }
 
-        if self.stage == Stage::Crossing && Instant::now().duration_since(self.time) > Duration::from_secs(800) {
+        if self.stage == Stage::Crossing && self.time > 800.0 {
             self.stage = Stage::Waiting;
         }
         
+        let entering = self.is_entering();
+        let platoon_coming = !self.is_held() && self.vehicles
+            .iter()
+            .any(|v| v.stage == Stage::Waiting && v.velocity > 0.0 && self.platoon.contains(&v.id));
         let vehicle_in_intersection = self
             .vehicles
             .iter_mut()
             .filter(|v| v.stage == Stage::Crossing)
             .collect::<Vec<&mut Vehicle>>();
 
-        if vehicle_in_intersection.is_empty() && self.is_vehicle_in_intersection {
+        // the route gives the intersection back once its vehicles are out, unless
+        // the next one is too close to stop before the line or the rest of its
+        // platoon is still on the way.
+        if vehicle_in_intersection.is_empty() && self.is_vehicle_in_intersection && !entering && !platoon_coming {
             self.stage = Stage::Waiting;
             self.is_vehicle_in_intersection = false;
         }
@@ -74,8 +159,113 @@ impl Route {
 
     }
 
+    /// Groups the vehicles waiting at the head of the route that follow each other
+    /// within `Settings::platoon_gap`, at most `Settings::platoon_size` of them:
+    /// the grant of the intersection lets them all in, the ones behind wait for
+    /// the next grant.
+    pub fn form_platoon(&mut self) {
+        self.platoon = self.next_platoon().iter().map(|v| v.id).collect();
+    }
+
+    /// The vehicles `Route::form_platoon` would group, from the head of the queue.
+    pub fn next_platoon(&self) -> Vec<&Vehicle> {
+        let mut platoon: Vec<&Vehicle> = Vec::new();
+        for vehicle in self.vehicles.iter().filter(|v| v.stage == Stage::Waiting) {
+            if let Some(leader) = platoon.last() {
+                let gap = vehicle.distance(leader) - (leader.length - self.settings.vehicle) as f64;
+                if platoon.len() >= self.settings.platoon_size || gap > self.settings.platoon_gap {
+                    break;
+                }
+            }
+            platoon.push(vehicle);
+        }
+        platoon
+    }
+
+    /// Seconds the vehicles in the intersection and the rest of the platoon take,
+    /// at full speed, to be out of it: a vehicle has the side of the intersection
+    /// and its own length to drive from the stop line.
+    pub fn clearing_time(&self) -> f64 {
+        let side = self.crossing_length();
+        self.vehicles
+            .iter()
+            .filter(|v| v.stage == Stage::Crossing || (v.stage == Stage::Waiting && self.platoon.contains(&v.id)))
+            .map(|v| {
+                let left = match v.stage {
+                    Stage::Waiting => side + to_stop_line(self.stop_point, v.position),
+                    _ => side - v.distance_to(self.stop_anchor()),
+                } + v.length as f64;
+                let fastest = v.velosity_type[v.velosity_type.len() - 1] as f64;
+                left.max(0.0) / fastest * TICK
+            })
+            .fold(0.0, f64::max)
+    }
+
+    /// The longest `clearing_time` the route can have: a full platoon of the
+    /// slowest class, `Settings::platoon_gap` apart, coming from its stop line.
+    pub fn longest_clearing_time(&self) -> f64 {
+        let settings = &self.settings;
+        CLASSES
+            .iter()
+            .map(|spec| {
+                let length = spec.length * settings.vehicle as f64;
+                let spread = settings.platoon_size.saturating_sub(1) as f64 * (settings.platoon_gap + length);
+                (self.crossing_length() + spread + length) / spec.speeds[3] as f64 * TICK
+            })
+            .fold(0.0, f64::max)
+    }
+
+    /// Distance from the stop line of the route to the one across the intersection.
+    pub fn crossing_length(&self) -> f64 {
+        to_stop_line(self.stop_point, Approach::from(self.cross).opposite().stop_point(&self.settings))
+    }
+
+    /// Distance from `vehicle` to the stop line of the route.
+    pub fn to_stop_line(&self, vehicle: &Vehicle) -> f64 {
+        to_stop_line(self.stop_point, vehicle.position)
+    }
+
+    /// None of the vehicles of the platoon is still before the stop line.
+    pub fn is_platoon_in(&self) -> bool {
+        !self.vehicles.iter().any(|v| v.stage == Stage::Waiting && self.platoon.contains(&v.id))
+    }
+
+    /// The controller doesn't let new vehicles of the route in the intersection.
+    pub fn is_held(&self) -> bool {
+        self.preempted || self.exit_blocked || self.yielding
+    }
+
+    /// A vehicle of the route is in the intersection, or too close to its stop
+    /// line to stop before it: the stage of a vehicle follows its position a tick late.
+    pub fn is_entering(&self) -> bool {
+        self.vehicles.iter().any(|v| v.stage == Stage::Crossing
+            || (v.stage == Stage::Waiting && v.velocity > 0.0
+                && to_stop_line(self.stop_point, v.position) < v.velocity.ceil() as f64 + 1.0))
+    }
+
+    /// Seconds the vehicles of the route have been waiting for the intersection at `time`.
+    pub fn waited(&self, time: f64) -> f64 {
+        self.waiting_since.map_or(0.0, |since| time - since)
+    }
+
+    /// The polyline followed by the vehicles of this route, in vehicle positions:
+    /// where they spawn, where they turn if they do, and where they leave the map.
+    pub fn path(&self) -> Vec<Point> {
+        Approach::from(self.cross).path(self.itineraire, &self.settings)
+    }
+
+    /// Where the path of the route meets its stop line.
+    pub fn stop_anchor(&self) -> Point {
+        let start = self.path()[0];
+        match self.cross {
+            Cross::First | Cross::Fourth => Point::new(start.x, self.stop_point.y),
+            Cross::Second | Cross::Third => Point::new(self.stop_point.x, start.y),
+        }
+    }
+
     pub fn distance_to_stop_point(&self) -> usize {
-        for i in 0..self.vehicles.len() {
+        // the U-turns drive back past the stop line once they have turned.
+        for i in (0..self.vehicles.len()).filter(|i| !self.vehicles[*i].is_changed_direction) {
             match self.cross {
                 Cross::First => {
                     if self.vehicles[i].position.y < self.stop_point.y {
@@ -104,11 +294,7 @@ impl Route {
     }
     
 
-    pub fn adjust_velocity_vehicle_in_route(&mut self, statistic: &mut Statistics) {
-        if self.itineraire == Itineraire::Right {
-            return;
-        }
-
+    pub fn adjust_velocity_vehicle_in_route<R: Rng>(&mut self, statistic: &mut Statistics, rng: &mut R) {
         // test all use case of cmp
         // check everywhere you use self.stop_point
         let cmp = |point_1: Point, point_2: Point| -> f64 {
@@ -120,60 +306,127 @@ impl Route {
         };
 
         // - filter the vehicle that are already cross and random their vilosity.
-        let mut rng = rand::thread_rng();
         for vehicle in self.vehicles.iter_mut().filter(|v| v.stage == Stage::Crossed) {
-            vehicle.velocity = vehicle.velosity_type[rng.gen_range(2,4)]
+            let velocity = vehicle.velosity_type[rng.gen_range(2,4)];
+            vehicle.accelerate_to(velocity);
         } 
 
         if self.stage == Stage::Crossing && self.vehicles.len() != 0 {
             self.detected_collisons = false;
             self.other_route_crossed = false;
             // && cmp(self.stop_point, v.position) < 2.0 * self.settings.safety_distance)
+            let held = self.is_held();
+            let platoon = &self.platoon;
             for vehicle in self.vehicles.iter_mut()
                 .filter(|v| v.stage == Stage::Crossing || 
-                    (v.stage == Stage::Waiting)) {
+                    (v.stage == Stage::Waiting && !held && platoon.contains(&v.id))) {
                 vehicle.set_vilosity(Vilosity::Fast);
             }
+
+            // a held route only lets the vehicles already inside drive out, the
+            // others wait for the next grant behind the platoon.
+            for vehicle in self.vehicles.iter_mut()
+                .filter(|v| v.stage == Stage::Waiting && (held || !platoon.contains(&v.id))
+                    && cmp(self.stop_point, v.position) < self.settings.safety_distance) {
+                hold(vehicle, cmp(self.stop_point, vehicle.position));
+            }
         } else if self.stage == Stage::Waiting && self.vehicles.len() != 0 {
             
-            // Réduire la vitesse des véhicules qui sont à moins de safety_distance
+            // Réduire la vitesse des véhicules qui sont à moins de safety_distance,
+            // the right turners that merged stay that close to the stop line.
+            let slot = self.slot;
             for vehicle in self.vehicles.iter_mut()
-                .filter(|v| v.stage == Stage::Crossing ||  cmp(self.stop_point, v.position) < self.settings.safety_distance) {
+                .filter(|v| v.stage == Stage::Crossing
+                    || (v.stage == Stage::Waiting && cmp(self.stop_point, v.position) < hold_distance(v, self.settings.safety_distance, slot))) {
                     if !self.detected_collisons && self.other_route_crossed {
                         statistic.close_calls += 1;
                         self.detected_collisons = true;
                     }
-                vehicle.set_vilosity(Vilosity::Reduce);
+                hold(vehicle, cmp(self.stop_point, vehicle.position));
             }
         }
     }
 
-    pub fn update(&mut self, canvas: &mut Canvas<Window>, texture: &Vec<Texture>, statistic: &mut Statistics) {
+    /// `vehicles` are all the vehicles of the intersection, the ones of the other
+    /// routes sharing a lane with this one are followed as well. `time` is the
+    /// simulation time at the start of the tick, `rng` draws the speeds of the
+    /// vehicles out of the intersection.
+    pub fn update<R: Rng>(&mut self, statistic: &mut Statistics, crosswalks: &[Rect], vehicles: &[Vehicle], time: f64, dt: f64, rng: &mut R) {
+        self.time += dt;
         self.set_stage();
-        self.adjust_velocity_vehicle_in_route(statistic);
+        if self.stage != Stage::Crossing {
+            self.platoon.clear();
+        } else if self.platoon.is_empty() {
+            self.form_platoon();
+        }
+        self.adjust_velocity_vehicle_in_route(statistic, rng);
 
-        for i in (0..self.vehicles.len()).rev() {
-            if i > 0 {
-                if let Some(other) = &self.vehicles.clone().get(i - 1) {
-                    self.vehicles[i].adjust_velocity(other);
+        // from the head of the queue, so each vehicle follows the speed the one
+        // ahead ends up with: the classes don't share the same speeds. Right
+        // turners included, every vehicle gives way to the pedestrians, ...
+        let others: Vec<&Vehicle> = vehicles.iter().filter(|v| !self.vehicles.iter().any(|own| own.id == v.id)).collect();
+        let stopped = self.stage != Stage::Crossing || self.is_held();
+        let (stop_point, safety_distance, slot) = (self.stop_point, self.settings.safety_distance, self.slot);
+        for i in 0..self.vehicles.len() {
+            let (ahead, behind) = self.vehicles.split_at_mut(i);
+            let vehicle = &mut behind[0];
+            let leader = leader(vehicle, ahead, &others);
+            // nor does a vehicle drive into the back of the one it follows, on a
+            // short leg the back of a long vehicle that has turned is in the way.
+            let mut areas = crosswalks.to_vec();
+            // the vehicles coming to a route that waits for its slot slow down to
+            // be `safety_distance` from the stop line when it opens, and drive on at
+            // that speed: the grant comes a little after. The leader still has the
+            // last word.
+            let distance = to_stop_line(stop_point, vehicle.position);
+            if let Some(slot) = slot {
+                if vehicle.stage == Stage::Waiting && distance > safety_distance {
+                    vehicle.advise(distance - safety_distance, slot);
                 }
             }
-            self.vehicles[i].update(canvas, &texture);
+            if let Some(leader) = leader {
+                vehicle.adjust_velocity(leader);
+                areas.extend(leader.footprint());
+            }
+            // following a vehicle of another route doesn't take it over the stop line.
+            let stopped = stopped || !self.platoon.contains(&vehicle.id);
+            if stopped && vehicle.stage == Stage::Waiting && distance < hold_distance(vehicle, safety_distance, slot) {
+                hold(vehicle, distance);
+            }
+            areas.extend(ahead.last().map(|leader| leader.footprint()).unwrap_or_default());
+            vehicle.yield_to(&areas);
+        }
+
+        for i in (0..self.vehicles.len()).rev() {
+            self.vehicles[i].update(dt);
 
             // Remove vehicles that have reached the end of the lane
             if self.vehicles[i].has_reached_end() {
-                statistic.retrieve(&self.vehicles[i]);
+                statistic.retrieve(&self.vehicles[i], time + dt);
                 self.vehicles.remove(i);
             }
         }
     }
 
-    pub fn add_vehicle(&mut self, route: Direction) {
-        let mut vehicle = Vehicle::new(route, self.itineraire, self.settings.clone());
+    pub fn draw(&self, canvas: &mut Canvas<Window>, texture: &[Texture]) {
+        for vehicle in self.vehicles.iter() {
+            vehicle.draw(canvas, texture);
+        }
+    }
+
+    pub fn add_vehicle<R: Rng>(&mut self, route: Direction, rng: &mut R) {
+        let class = VehicleClass::pick_with(&self.settings.mix, rng);
+        self.add_vehicle_of_class(route, class, rng);
+    }
+
+    pub fn add_vehicle_of_class<R: Rng>(&mut self, route: Direction, class: VehicleClass, rng: &mut R) {
+        let mut vehicle = Vehicle::with_class(route, self.itineraire, class, self.settings.clone(), rng);
         vehicle.spawn(route);
 
         if let Some(last) = self.vehicles.clone().last() {
-            if self.settings.safety_distance < vehicle.distance(last) && self.vehicles.len() < 5 {
+            // keep the safety distance behind the back of the last vehicle.
+            let gap = vehicle.distance(last) - (last.length - self.settings.vehicle) as f64;
+            if self.settings.safety_distance < gap && self.vehicles.len() < 5 {
                 self.vehicles.push(vehicle);
             }
         } else {
@@ -0,0 +1,165 @@
+use crate::{
+    accepts_gap, extract_routes_mut, head_of_lane, lane::Stage, merge_right_turns, preempt_for_emergency, prevent_starvation,
+    conflicts, Cross, Itineraire, Lane, Statistics, TICK,
+};
+
+/// Most nodes of the search tree explored for one decision, past it the best
+/// schedule found so far is kept and the search reported as truncated.
+pub const MAX_NODES: usize = 20_000;
+
+/// Seconds ahead the platoons coming to the intersection are scheduled.
+const HORIZON: f64 = 10.0;
+
+/// A platoon of a route to let through the intersection.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Job {
+    pub lane: (Cross, Itineraire),
+    /// Seconds until the head of the platoon can be on the stop line.
+    pub release: f64,
+    /// Seconds from the head on the stop line to the whole platoon out of the intersection.
+    pub duration: f64,
+    /// Vehicles in the platoon, each one counts its delay.
+    pub weight: f64,
+}
+
+struct Search<'a> {
+    jobs: &'a [Job],
+    busy: &'a [((Cross, Itineraire), f64)],
+    best: Option<(f64, Vec<f64>)>,
+    nodes: usize,
+    max_nodes: usize,
+    truncated: bool,
+}
+
+impl Search<'_> {
+    /// The earliest `j` can start after the jobs of `starts` and the routes holding the intersection.
+    fn ready(&self, j: usize, starts: &[Option<f64>]) -> f64 {
+        let lane = self.jobs[j].lane;
+        let busy = self.busy.iter().filter(|(other, _)| conflicts(lane, *other)).map(|(_, until)| *until);
+        let scheduled = starts
+            .iter()
+            .enumerate()
+            .filter_map(|(k, start)| start.filter(|_| conflicts(lane, self.jobs[k].lane)).map(|start| start + self.jobs[k].duration));
+        busy.chain(scheduled).fold(self.jobs[j].release, f64::max)
+    }
+
+    /// Schedules the jobs left one at a time, `last` the one scheduled last and
+    /// `delay` the weighted delay of the ones scheduled so far.
+    fn branch(&mut self, starts: &mut Vec<Option<f64>>, last: Option<usize>, delay: f64) {
+        self.nodes += 1;
+        let mut open: Vec<(f64, usize)> = (0..self.jobs.len())
+            .filter(|j| starts[*j].is_none())
+            .map(|j| (self.ready(j, starts), j))
+            .collect();
+        if open.is_empty() {
+            if self.best.as_ref().is_none_or(|(best, _)| delay < *best) {
+                self.best = Some((delay, starts.iter().map(|start| start.unwrap_or(0.0)).collect()));
+            }
+            return;
+        }
+
+        // the jobs left only start later than they could now.
+        let bound = delay + open.iter().map(|(ready, j)| self.jobs[*j].weight * (ready - self.jobs[*j].release)).sum::<f64>();
+        if let Some((best, _)) = &self.best {
+            if bound >= *best {
+                return;
+            }
+            if self.nodes >= self.max_nodes {
+                self.truncated = true;
+                return;
+            }
+        }
+
+        open.sort_by(|a, b| a.0.total_cmp(&b.0));
+        for (ready, j) in open {
+            // two jobs that don't conflict start at the same times in either order.
+            if last.is_some_and(|i| j < i && !conflicts(self.jobs[i].lane, self.jobs[j].lane)) {
+                continue;
+            }
+            starts[j] = Some(ready);
+            self.branch(starts, Some(j), delay + self.jobs[j].weight * (ready - self.jobs[j].release));
+            starts[j] = None;
+        }
+    }
+}
+
+/// The start of each job, in seconds from now, that minimizes the delay of all
+/// their vehicles: two jobs whose paths cross never hold the intersection at the
+/// same time, nor does a job with one of the `busy` routes until it is out.
+/// Explores the orders of the jobs by branch and bound, and says whether it went
+/// through all of them: past `MAX_NODES` the starts are the best found.
+pub fn schedule(jobs: &[Job], busy: &[((Cross, Itineraire), f64)]) -> (Vec<f64>, bool) {
+    schedule_with_limit(jobs, busy, MAX_NODES)
+}
+
+/// `schedule` exploring at most `max_nodes` nodes of the search tree.
+pub fn schedule_with_limit(jobs: &[Job], busy: &[((Cross, Itineraire), f64)], max_nodes: usize) -> (Vec<f64>, bool) {
+    let mut search = Search { jobs, busy, best: None, nodes: 0, max_nodes, truncated: false };
+    search.branch(&mut vec![None; jobs.len()], None, 0.0);
+    (search.best.map_or(Vec::new(), |(_, starts)| starts), !search.truncated)
+}
+
+/// Schedules the next platoon of every route coming to the intersection over a
+/// rolling horizon, and gives the intersection to the ones due first. The others
+/// are told their start as the slot of the speed advisory.
+pub fn optimal_schedule(lanes: &mut Vec<Lane>, time: f64, statistic: &mut Statistics) {
+    prevent_starvation(lanes, time);
+    preempt_for_emergency(lanes);
+    merge_right_turns(lanes);
+
+    let routes = || lanes.iter().flat_map(|lane| lane.routes.iter());
+    let busy: Vec<((Cross, Itineraire), f64)> = routes()
+        .filter(|route| route.stage == Stage::Crossing)
+        .map(|route| ((route.cross, route.itineraire), route.clearing_time()))
+        .collect();
+
+    // right turns only merge, and a route behind a vehicle of the one sharing its
+    // lane goes after it.
+    let mut jobs = Vec::new();
+    let mut leads = Vec::new();
+    for route in routes().filter(|r| r.stage != Stage::Crossing && r.itineraire != Itineraire::Right) {
+        let lane = (route.cross, route.itineraire);
+        let platoon = route.next_platoon();
+        let (Some(head), Some(last)) = (platoon.first(), platoon.last()) else { continue };
+        if head_of_lane(lanes, lane) != lane {
+            continue;
+        }
+
+        let speed = platoon.iter().map(|v| v.velosity_type[v.velosity_type.len() - 1] as f64).fold(f64::INFINITY, f64::min) / TICK;
+        let release = route.to_stop_line(head) / speed;
+        if release > HORIZON {
+            continue;
+        }
+        let length = route.to_stop_line(last) - route.to_stop_line(head) + route.crossing_length() + last.length as f64;
+        jobs.push(Job { lane, release, duration: length / speed, weight: platoon.len() as f64 });
+        // the platoon is given the intersection as its head gets close to the line.
+        leads.push(route.settings.safety_distance / speed);
+    }
+
+    let (starts, complete) = schedule(&jobs, &busy);
+    if !jobs.is_empty() {
+        statistic.record_schedule(complete);
+    }
+    let gaps: Vec<bool> = jobs.iter().map(|job| job.lane.1 != Itineraire::UTurn || accepts_gap(lanes, job.lane)).collect();
+
+    for route in extract_routes_mut(lanes) {
+        route.slot = None;
+        let lane = (route.cross, route.itineraire);
+        let Some(j) = jobs.iter().position(|job| job.lane == lane) else { continue };
+        if route.is_held() {
+            continue;
+        }
+
+        let first = !jobs
+            .iter()
+            .enumerate()
+            .any(|(k, other)| conflicts(lane, other.lane) && (starts[k], k) < (starts[j], j));
+        let free = !busy.iter().any(|(other, _)| conflicts(lane, *other));
+        if first && free && gaps[j] && starts[j] <= leads[j] {
+            route.time = 0.0;
+            route.stage = Stage::Crossing;
+        } else if route.settings.speed_advisory && starts[j] > jobs[j].release {
+            route.slot = Some(starts[j]);
+        }
+    }
+}
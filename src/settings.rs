@@ -1,115 +1,326 @@
-use std::path::Path;
+use std::{collections::HashSet, path::Path, time::Duration};
 
-use sdl2::{event::Event, image::{self, InitFlag, LoadTexture}, keyboard::Keycode, pixels::Color, rect::Rect, render::{Texture, TextureCreator}, video::WindowContext};
+use sdl2::{event::Event, image::{self, InitFlag, LoadTexture}, keyboard::Keycode, pixels::Color, rect::{Point, Rect}, render::{Texture, TextureCreator, WindowCanvas}, ttf::Font, video::WindowContext};
 
-use crate::{Cross, Itineraire, Vehicle};
+use crate::charts::{draw_histogram, draw_line_chart, draw_text, Series};
+use crate::{lane::Stage, stats::{Aggregate, Histogram}, Cross, Itineraire, Lane, Vehicle, VehicleClass, CAR_SPRITES, CLASSES};
 use sdl2::render::TextureQuery;
 
+/// How often, in seconds of simulation, the time series are sampled.
+const SAMPLE_PERIOD: f64 = 1.0;
+/// Number of samples the throughput is averaged over in the chart.
+const THROUGHPUT_WINDOW: usize = 10;
+const PAGES: usize = 5;
+
+/// Snapshot of the intersection taken every `SAMPLE_PERIOD`.
+#[derive(Debug, Clone)]
+pub struct Sample {
+    pub time: f64,
+    pub passed: usize,
+    /// Vehicles that have not entered the intersection yet, per approach.
+    pub queues: Vec<(Cross, usize)>,
+}
+
 #[derive(Debug, Clone)]
 pub struct Statistics {
     pub max_vehicles_passed: usize,
-    pub max_velocities: Vec<f64>,
-    pub min_velocities: Vec<f64>,
-    pub time_to_pass: Vec<f64>,
     pub max_velocity: f64,
     pub min_velocity: f64,
-    pub max_time_to_pass: f64,
-    pub min_time_to_pass: f64,
+    pub speed: Aggregate,
+    pub time_to_pass: Aggregate,
+    pub speed_histogram: Histogram,
+    pub time_to_pass_histogram: Histogram,
+    pub samples: Vec<Sample>,
     pub close_calls: usize,
+    pub collisions: usize,
+    /// Delay of the emergency vehicles, see `Vehicle::delay`.
+    pub emergency_delay: Aggregate,
+    /// Seconds the vehicles spent held on routes the pre-emption for an emergency
+    /// vehicle stopped while they had the intersection.
+    pub preemption_delay: f64,
+    /// Time the pedestrians waited on the curb before crossing.
+    pub pedestrian_wait: Aggregate,
+    pub pedestrians_crossed: usize,
+    /// Vehicles that came down to a crawl before the stop line.
+    pub stops: usize,
+    /// Vehicles the speed advisory slowed down that never stopped before the line:
+    /// advised and not stopped, not compared with a run without the advisory.
+    pub stops_avoided: usize,
+    /// The vehicles spawned before its start or leaving after its end are left
+    /// out of the vehicle statistics, the collisions and close calls all count.
+    pub window: (f64, f64),
+    /// Decisions of the optimal schedule, and the ones whose search stopped at
+    /// its node limit: their schedule is the best found, not the optimal one.
+    pub schedules: usize,
+    pub truncated_schedules: usize,
+    colliding: HashSet<(usize, usize)>,
 }
 
 impl Statistics {
     pub fn new() -> Self {
         Self {
             max_vehicles_passed: 0,
-            max_velocities: Vec::new(),
-            min_velocities: Vec::new(),
-            time_to_pass: Vec::new(),
             max_velocity: 0.0,
             min_velocity: 0.0,
-            max_time_to_pass: 0.0,
-            min_time_to_pass: 0.0,
+            speed: Aggregate::new(),
+            time_to_pass: Aggregate::new(),
+            speed_histogram: Histogram::new(10.0),
+            time_to_pass_histogram: Histogram::new(1.0),
+            samples: Vec::new(),
             close_calls: 0,
+            collisions: 0,
+            emergency_delay: Aggregate::new(),
+            preemption_delay: 0.0,
+            pedestrian_wait: Aggregate::new(),
+            pedestrians_crossed: 0,
+            stops: 0,
+            stops_avoided: 0,
+            window: (0.0, f64::INFINITY),
+            schedules: 0,
+            truncated_schedules: 0,
+            colliding: HashSet::new(),
         }
     }
 
-    pub fn retrieve(&mut self, vehicle: &Vehicle) {
+    /// Counts `vehicle` leaving the intersection at `time`, if it was there
+    /// within the measurement window.
+    pub fn retrieve(&mut self, vehicle: &Vehicle, time: f64) {
+        if time - vehicle.time < self.window.0 || time > self.window.1 {
+            return;
+        }
+
+        // Update max and min velocities: the fastest and slowest instant seen by any vehicle.
+        if self.max_vehicles_passed == 0 {
+            self.max_velocity = vehicle.max_vilosity;
+            self.min_velocity = vehicle.min_vilosity;
+        } else {
+            self.max_velocity = self.max_velocity.max(vehicle.max_vilosity);
+            self.min_velocity = self.min_velocity.min(vehicle.min_vilosity);
+        }
         self.max_vehicles_passed += 1;
-        // Update max and min velocities
-        self.max_velocities.push(vehicle.max_vilosity as f64);
-        self.min_velocities.push(vehicle.min_vilosity as f64);
 
-        // Update max and min times to pass the intersection
+        // the average speed of the vehicle over its whole trip.
+        if vehicle.time > 0.0 {
+            let speed = vehicle.distance_traveled / vehicle.time;
+            self.speed.push(speed);
+            self.speed_histogram.push(speed);
+        }
+
+        // Update the times to pass the intersection
         self.time_to_pass.push(vehicle.time);
-        self.max_time_to_pass = self.time_to_pass.iter().cloned().fold(f64::MIN, f64::max);
-        self.min_time_to_pass = self.time_to_pass.iter().cloned().fold(f64::MAX, f64::min);
+        self.time_to_pass_histogram.push(vehicle.time);
+
+        if vehicle.class == VehicleClass::Emergency {
+            self.emergency_delay.push(vehicle.delay());
+        }
 
-        self.min_velocity = self.min_velocities.iter().cloned().fold(f64::MIN, f64::max);
-        self.max_velocity = self.max_velocities.iter().cloned().fold(f64::MAX, f64::min);
+        if vehicle.has_stopped {
+            self.stops += 1;
+        } else if vehicle.advised {
+            self.stops_avoided += 1;
+        }
     }
 
-    pub fn display_statistics_window(&self, event_pump: &mut sdl2::EventPump) {
-        const WIDTH: u32 = 600;
-        const HEIGHT: u32 = 400;
-        let sdl_context = sdl2::init().unwrap();
-        let video_subsystem = sdl_context.video().unwrap();
-    
-        let window = video_subsystem
-            .window("Simulation Statistics", WIDTH, HEIGHT)
-            .position_centered()
-            .build()
-            .unwrap();
-    
-        let mut canvas = window.into_canvas().build().unwrap();
-        canvas.set_draw_color(Color::RGB(0, 0, 0));
-        canvas.clear();
-    
-        let ttf_context = sdl2::ttf::init().unwrap();
-        let mut font = ttf_context.load_font("assets/Roboto-Thin.ttf", 30).unwrap();
-        font.set_style(sdl2::ttf::FontStyle::BOLD);
-    
+    /// Counts a decision of the optimal schedule, `complete` when its search
+    /// went through every order that could do better.
+    pub fn record_schedule(&mut self, complete: bool) {
+        self.schedules += 1;
+        if !complete {
+            self.truncated_schedules += 1;
+        }
+    }
+
+    /// Adds `dt` for every vehicle slowed down before the stop line of a route the
+    /// pre-emption interrupted, see `Route::interrupted`. The routes that were
+    /// waiting anyway owe their wait to the controller.
+    pub fn record_preemption(&mut self, lanes: &[Lane], dt: f64) {
+        let held = lanes
+            .iter()
+            .flat_map(|lane| lane.routes.iter())
+            .filter(|route| route.preempted && route.interrupted)
+            .flat_map(|route| route.vehicles.iter())
+            .filter(|v| v.stage == Stage::Waiting && v.velocity < v.velosity_type[2])
+            .count();
+        self.preemption_delay += held as f64 * dt;
+    }
+
+    /// Records a `Sample` when a sampling period has elapsed since the last one.
+    pub fn sample(&mut self, lanes: &[Lane], sim_time: f64) {
+        if let Some(last) = self.samples.last() {
+            if sim_time - last.time < SAMPLE_PERIOD {
+                return;
+            }
+        }
+
+        let queues = lanes
+            .iter()
+            .map(|lane| {
+                let queue = lane.routes
+                    .iter()
+                    .flat_map(|route| route.vehicles.iter())
+                    .filter(|v| v.stage == Stage::Waiting)
+                    .count();
+                (lane.cross, queue)
+            })
+            .collect();
+
+        self.samples.push(Sample {
+            time: sim_time,
+            passed: self.max_vehicles_passed,
+            queues,
+        });
+    }
+
+    /// Takes the pairs of vehicle ids overlapping this frame, a pair only counts
+    /// as a new collision on the frame it starts overlapping.
+    pub fn record_collisions(&mut self, overlapping: HashSet<(usize, usize)>) {
+        self.collisions += overlapping.difference(&self.colliding).count();
+        self.colliding = overlapping;
+    }
+
+    fn draw_summary(&self, canvas: &mut WindowCanvas, font: &Font, width: u32) -> Result<(), String> {
         let stats_text = vec![
             format!("Statistics"),
             format!("Max vehicles : {}",self.max_vehicles_passed),
             format!("Max velocity : {:.2} m/s", self.max_velocity),
             format!("Min velocity : {:.2} m/s", self.min_velocity),
-            format!("Max time to pass : {:.2} s",self.max_time_to_pass),
-            format!("Min time to pass : {:.2} s",self.min_time_to_pass),
-            format!("Collisions : {}", 0),
-            format!("Close calls : {}",self.close_calls)
+            format!("Speed mean/p50/p90/p99 : {:.1} / {:.1} / {:.1} / {:.1}",
+                self.speed.mean, self.speed.p50(), self.speed.p90(), self.speed.p99()),
+            format!("Max time to pass : {:.2} s",self.time_to_pass.max),
+            format!("Min time to pass : {:.2} s",self.time_to_pass.min),
+            format!("Pass time mean/p50/p90/p99 : {:.1} / {:.1} / {:.1} / {:.1}",
+                self.time_to_pass.mean, self.time_to_pass.p50(), self.time_to_pass.p90(), self.time_to_pass.p99()),
+            format!("Collisions : {}", self.collisions),
+            format!("Close calls : {}",self.close_calls),
+            format!("Stops / advised without stopping : {} / {}", self.stops, self.stops_avoided),
+            format!("Emergency delay mean/max : {:.1} / {:.1} s ({} vehicles)",
+                self.emergency_delay.mean, self.emergency_delay.max, self.emergency_delay.count),
+            format!("Delay caused by pre-emption : {:.1} s", self.preemption_delay),
+            format!("Pedestrian wait mean/p90/max : {:.1} / {:.1} / {:.1} s ({} crossed)",
+                self.pedestrian_wait.mean, self.pedestrian_wait.p90(), self.pedestrian_wait.max, self.pedestrians_crossed),
         ];
 
         for (i, stat_text) in stats_text.iter().enumerate() {
             let surface = font.render(&stat_text)
                 .blended(Color::RGB(255, 255, 255))
-                .unwrap();
+                .map_err(|e| e.to_string())?;
             let texture_creator = canvas.texture_creator();
-            let texture = texture_creator.create_texture_from_surface(&surface).unwrap();
+            let texture = texture_creator.create_texture_from_surface(&surface).map_err(|e| e.to_string())?;
     
-            let TextureQuery { width, height, .. } = texture.query();
+            let TextureQuery { width: text_width, height, .. } = texture.query();
             
             let (x, y) = if i == 0 {
-                ((WIDTH - width) as i32 / 2, 24 + 30 * i as i32)
+                ((width - text_width) as i32 / 2, 24 + 30 * i as i32)
             } else {
-                (30, 24 + 40 * i as i32)
+                (30, 24 + 34 * i as i32)
             };
 
-            let r = Rect::new(x, y, width, height);
+            let r = Rect::new(x, y, text_width, height);
     
-            canvas.copy(&texture, None, r).unwrap();
+            canvas.copy(&texture, None, r)?;
+        }
+
+        Ok(())
+    }
+
+    /// Vehicles leaving the intersection per minute, averaged over `THROUGHPUT_WINDOW` samples.
+    fn throughput_series(&self) -> Vec<(f64, f64)> {
+        (1..self.samples.len())
+            .map(|i| {
+                let from = &self.samples[i.saturating_sub(THROUGHPUT_WINDOW)];
+                let to = &self.samples[i];
+                let rate = (to.passed - from.passed) as f64 * 60.0 / (to.time - from.time);
+                (to.time, rate)
+            })
+            .collect()
+    }
+
+    fn queue_series(&self) -> Vec<Series> {
+        let colors = [
+            Color::RGB(230, 90, 90),
+            Color::RGB(90, 200, 90),
+            Color::RGB(90, 160, 230),
+            Color::RGB(230, 200, 80),
+        ];
+
+        let approaches = self.samples.first().map(|s| s.queues.len()).unwrap_or(0);
+        (0..approaches)
+            .map(|i| {
+                let name = self.samples[0].queues[i].0.name().to_string();
+                let points = self.samples
+                    .iter()
+                    .map(|s| (s.time, s.queues[i].1 as f64))
+                    .collect();
+                (name, colors[i % colors.len()], points)
+            })
+            .collect()
+    }
+
+    fn draw_page(&self, page: usize, canvas: &mut WindowCanvas, title_font: &Font, font: &Font) -> Result<(), String> {
+        let (width, height) = canvas.output_size()?;
+        let area = Rect::new(0, 0, width, height - 30);
+
+        match page {
+            0 => self.draw_summary(canvas, title_font, width)?,
+            1 => draw_line_chart(canvas, font, area, "Throughput (veh/min)", "time (s)", &[
+                (String::from("all"), Color::RGB(90, 200, 90), self.throughput_series()),
+            ])?,
+            2 => draw_line_chart(canvas, font, area, "Queue length per approach", "time (s)", &self.queue_series())?,
+            3 => draw_histogram(canvas, font, area, "Time to pass", "time (s)", &self.time_to_pass_histogram)?,
+            _ => draw_histogram(canvas, font, area, "Average speed", "speed (px/s)", &self.speed_histogram)?,
         }
 
-        canvas.present();
+        let footer = format!("< {} / {} >   left / right arrows to change page", page + 1, PAGES);
+        draw_text(canvas, font, &footer, Point::new(30, height as i32 - 26), Color::RGB(160, 160, 160))
+    }
+
+    pub fn display_statistics_window(&self, event_pump: &mut sdl2::EventPump) {
+        const WIDTH: u32 = 800;
+        const HEIGHT: u32 = 560;
+        let sdl_context = sdl2::init().unwrap();
+        let video_subsystem = sdl_context.video().unwrap();
     
-        // let mut event_pump = sdl_context.event_pump().unwrap();
+        let window = video_subsystem
+            .window("Simulation Statistics", WIDTH, HEIGHT)
+            .position_centered()
+            .build()
+            .unwrap();
+    
+        let mut canvas = window.into_canvas().build().unwrap();
+    
+        let ttf_context = sdl2::ttf::init().unwrap();
+        let mut title_font = ttf_context.load_font("assets/Roboto-Thin.ttf", 30).unwrap();
+        title_font.set_style(sdl2::ttf::FontStyle::BOLD);
+        let mut font = ttf_context.load_font("assets/Roboto-Thin.ttf", 16).unwrap();
+        font.set_style(sdl2::ttf::FontStyle::BOLD);
+
+        let mut page = 0;
+        let mut redraw = true;
         'stats_window: loop {
             for event in event_pump.poll_iter() {
                 match event {
                     Event::Quit { .. }
                     | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => break 'stats_window,
+                    Event::KeyDown { keycode: Some(Keycode::Right), .. } => {
+                        page = (page + 1) % PAGES;
+                        redraw = true;
+                    }
+                    Event::KeyDown { keycode: Some(Keycode::Left), .. } => {
+                        page = (page + PAGES - 1) % PAGES;
+                        redraw = true;
+                    }
                     _ => {}
                 }
             }
+
+            if redraw {
+                canvas.set_draw_color(Color::RGB(0, 0, 0));
+                canvas.clear();
+                self.draw_page(page, &mut canvas, &title_font, &font).unwrap();
+                canvas.present();
+                redraw = false;
+            }
+            ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 30));
         }
     }
 }
@@ -125,6 +336,25 @@ pub struct Settings {
 
     pub vertical_key_points: Vec<i32>,
     pub horizontal_key_points: Vec<i32>,
+    /// Weight of each vehicle class among the spawned vehicles.
+    pub mix: Vec<(VehicleClass, u32)>,
+    /// Pedestrians arriving at each crosswalk per minute.
+    pub pedestrian_rate: f64,
+    /// Longest time, in seconds, a route waits for the intersection before the
+    /// routes crossing its path are held to let it go.
+    pub max_wait: f64,
+    /// Seconds from the start before the vehicle statistics are measured, the
+    /// intersection fills up meanwhile.
+    pub warm_up: f64,
+    /// Seconds the vehicle statistics are measured for after the warm-up.
+    pub measurement: f64,
+    /// Most vehicles of a route let in the intersection with one grant.
+    pub platoon_size: usize,
+    /// Longest gap, in pixels, between two vehicles of a route in the same platoon.
+    pub platoon_gap: f64,
+    /// The vehicles coming to a route that waits for the intersection slow down
+    /// to get there when it frees up rather than stopping at the line.
+    pub speed_advisory: bool,
 }
 
 impl Settings {
@@ -166,39 +396,104 @@ impl Settings {
             offset_road,
 
             vertical_key_points: get_map_key_points(width, half_width),
-            horizontal_key_points: get_map_key_points(height, half_height)
+            horizontal_key_points: get_map_key_points(height, half_height),
+            mix: vec![
+                (VehicleClass::Car, 70),
+                (VehicleClass::Bus, 10),
+                (VehicleClass::Truck, 10),
+                (VehicleClass::Motorbike, 10),
+            ],
+            pedestrian_rate: 2.0,
+            max_wait: 30.0,
+            warm_up: 0.0,
+            measurement: f64::INFINITY,
+            platoon_size: 4,
+            platoon_gap: 150.0,
+            speed_advisory: true,
+        }
+    }
+
+    /// Applies the options of a command line shared by the binaries:
+    /// `--mix car=70,bus=10,...` the weight of each vehicle class,
+    /// `--pedestrians <rate>` the pedestrians arriving at each crosswalk per minute,
+    /// `--max-wait <seconds>` the longest a route waits for the intersection,
+    /// `--warm-up <seconds>` and `--measure <seconds>` the window the vehicle
+    /// statistics are measured in, `--platoon <size>` and `--platoon-gap <pixels>`
+    /// the vehicles of a route let in together, and `--no-advisory` turns the
+    /// speed advisory off.
+    pub fn apply_args(&mut self, args: &[String]) -> Result<(), String> {
+        let option = |name: &str| args.iter().position(|a| a == name).and_then(|i| args.get(i + 1));
+        fn number<T: std::str::FromStr>(value: &str, what: &str) -> Result<T, String> {
+            value.parse().map_err(|_| format!("the {} {}", what, value))
+        }
+
+        if let Some(mix) = option("--mix") {
+            self.mix = VehicleClass::parse_mix(mix).map_err(|e| format!("the spawn mix {}", e))?;
+        }
+        if let Some(rate) = option("--pedestrians") {
+            self.pedestrian_rate = number(rate, "pedestrian rate")?;
+        }
+        if let Some(wait) = option("--max-wait") {
+            self.max_wait = number(wait, "maximum wait")?;
+        }
+        if let Some(warm_up) = option("--warm-up") {
+            self.warm_up = number(warm_up, "warm-up")?;
+        }
+        if let Some(measurement) = option("--measure") {
+            self.measurement = number(measurement, "measurement window")?;
         }
+        if let Some(size) = option("--platoon") {
+            self.platoon_size = number(size, "platoon size")?;
+            if self.platoon_size < 1 {
+                return Err(format!("the platoon size {}, a platoon has at least 1 vehicle", size));
+            }
+        }
+        if let Some(gap) = option("--platoon-gap") {
+            self.platoon_gap = number(gap, "platoon gap")?;
+        }
+        self.speed_advisory &= !args.iter().any(|a| a == "--no-advisory");
+        Ok(())
     }
 }
 
 
-pub fn cars_texture<'a>(texture_creator: &'a TextureCreator<WindowContext>) -> Vec<Texture<'a>> {
-    let _image_context = image::init(InitFlag::PNG | InitFlag::JPG).unwrap();
+/// The car sprites first, then one per class with a sprite of its own: the
+/// indices `VehicleClass::texture` gives. A sprite that doesn't load is an
+/// error, skipping it would shift the indices of the ones after it.
+pub fn cars_texture<'a>(texture_creator: &'a TextureCreator<WindowContext>) -> Result<Vec<Texture<'a>>, String> {
+    let _image_context = image::init(InitFlag::PNG | InitFlag::JPG)?;
 
-    let texture_names = vec![
+    let texture_names: [&str; CAR_SPRITES] = [
         "BlackOut.png", "WhiteStrip.png", "BlueStrip.png", "GreenStrip.png", "RedStrip.png", "PinkStrip.png",
     ];
 
     let mut cars_textures = Vec::new();
 
     for name in texture_names {
-        let texture_path = Path::new("assets").join(name);
-        match texture_creator.load_texture(texture_path) {
-            Ok(texture) => cars_textures.push(texture),
-            Err(e) => {
-                eprintln!("Failed to load texture {}: {}", name, e);
-            }
-        }
+        let texture = texture_creator
+            .load_texture(Path::new("assets").join(name))
+            .map_err(|e| format!("texture {}: {}", name, e))?;
+        cars_textures.push(texture);
     };
-    cars_textures
+
+    // the other classes reuse a car sprite, tinted with the colour of the class.
+    for (name, tint) in CLASSES.iter().filter_map(|spec| spec.sprite) {
+        let mut texture = texture_creator
+            .load_texture(Path::new("assets").join(name))
+            .map_err(|e| format!("texture {}: {}", name, e))?;
+        texture.set_color_mod(tint.r, tint.g, tint.b);
+        cars_textures.push(texture);
+    }
+    Ok(cars_textures)
 }
 
+/// The routes whose paths cross the path of `lane`, `lane` included.
 pub struct BLOCK<'a> {
     pub lane: (Cross, Itineraire),
     pub intersections: &'a [(Cross, Itineraire)],
 }
 
-pub(crate) const BLOCKS: &[&BLOCK] = &[
+pub const BLOCKS: &[&BLOCK] = &[
 
     // North
     &BLOCK{
@@ -209,6 +504,7 @@ pub(crate) const BLOCKS: &[&BLOCK] = &[
             (Cross::Fourth, Itineraire::Left),
             (Cross::Third, Itineraire::Left),
             (Cross::Third, Itineraire::Straight),
+            (Cross::Third, Itineraire::UTurn),
             (Cross::Second, Itineraire::Left),
         ],
     },
@@ -222,7 +518,7 @@ pub(crate) const BLOCKS: &[&BLOCK] = &[
             (Cross::Second, Itineraire::Left),
         ],
     },
-    // Right
+    // West
     &BLOCK{
         lane: (Cross::Second, Itineraire::Left),
         intersections: &[
@@ -232,6 +528,7 @@ pub(crate) const BLOCKS: &[&BLOCK] = &[
                 (Cross::Fourth, Itineraire::Left),
                 (Cross::Third, Itineraire::Straight),
                 (Cross::Third, Itineraire::Left),
+                (Cross::First, Itineraire::UTurn),
             ],
     },
     &BLOCK{
@@ -245,7 +542,7 @@ pub(crate) const BLOCKS: &[&BLOCK] = &[
         ],
     },
 
-    // Left
+    // East
     &BLOCK{
         lane: (Cross::Third, Itineraire::Left),
         intersections: &[
@@ -253,6 +550,7 @@ pub(crate) const BLOCKS: &[&BLOCK] = &[
             (Cross::First, Itineraire::Left),
             (Cross::Fourth, Itineraire::Straight),
             (Cross::Fourth, Itineraire::Left),
+            (Cross::Fourth, Itineraire::UTurn),
             (Cross::Second, Itineraire::Straight),
             (Cross::Second, Itineraire::Left),
         ],
@@ -278,6 +576,7 @@ pub(crate) const BLOCKS: &[&BLOCK] = &[
             (Cross::Third, Itineraire::Left),
             (Cross::Second, Itineraire::Straight),
             (Cross::Second, Itineraire::Left),
+            (Cross::Second, Itineraire::UTurn),
         ],
     },
     &BLOCK{
@@ -291,9 +590,66 @@ pub(crate) const BLOCKS: &[&BLOCK] = &[
         ],
     },
 
-  
+    // U-turns: they share the inner lane with the left turns and only meet, where
+    // they head back, the left turns of the approach on their left.
+    &BLOCK{
+        lane: (Cross::First, Itineraire::UTurn),
+        intersections: &[
+            (Cross::First, Itineraire::UTurn),
+            (Cross::Second, Itineraire::Left),
+        ],
+    },
+    &BLOCK{
+        lane: (Cross::Second, Itineraire::UTurn),
+        intersections: &[
+            (Cross::Second, Itineraire::UTurn),
+            (Cross::Fourth, Itineraire::Left),
+        ],
+    },
+    &BLOCK{
+        lane: (Cross::Third, Itineraire::UTurn),
+        intersections: &[
+            (Cross::Third, Itineraire::UTurn),
+            (Cross::First, Itineraire::Left),
+        ],
+    },
+    &BLOCK{
+        lane: (Cross::Fourth, Itineraire::UTurn),
+        intersections: &[
+            (Cross::Fourth, Itineraire::UTurn),
+            (Cross::Third, Itineraire::Left),
+        ],
+    },
 ];
 
+/// Index of a route in `CONFLICTS`.
+const fn route_index((cross, itineraire): (Cross, Itineraire)) -> usize {
+    cross as usize * 4 + itineraire as usize
+}
+
+/// `BLOCKS` as a table: `CONFLICTS[a][b]` when the paths of the routes `a` and
+/// `b` cross, see `route_index`.
+const CONFLICTS: [[bool; 16]; 16] = {
+    let mut table = [[false; 16]; 16];
+    let mut i = 0;
+    while i < BLOCKS.len() {
+        let lane = route_index(BLOCKS[i].lane);
+        let mut j = 0;
+        while j < BLOCKS[i].intersections.len() {
+            let other = route_index(BLOCKS[i].intersections[j]);
+            table[lane][other] = lane != other;
+            j += 1;
+        }
+        i += 1;
+    }
+    table
+};
+
+/// Whether the paths of the two routes cross, see `BLOCKS`.
+pub fn conflicts(a: (Cross, Itineraire), b: (Cross, Itineraire)) -> bool {
+    CONFLICTS[route_index(a)][route_index(b)]
+}
+
 /*
     // block 1
 
@@ -0,0 +1,195 @@
+use std::rc::Rc;
+
+use rand::{Rng, SeedableRng, StdRng};
+use sdl2::rect::Rect;
+
+use crate::{
+    check_invariants, detect_collisions, Approach, Controller, Cross, Crosswalk, Itineraire, Lane, Settings, Statistics, Vehicle,
+    VehicleClass,
+};
+
+/// Length of a simulation tick in seconds, vehicle velocities are in pixels per tick.
+pub const TICK: f64 = 1.0 / 60.0;
+
+/// The time scales the clock steps through.
+const SCALES: [f64; 6] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+
+/// Never run more ticks than this in a single frame, so a slow frame can't
+/// make the simulation spiral behind the wall clock.
+const MAX_TICKS_PER_FRAME: usize = 64;
+
+/// The intersection without any rendering: advanced one fixed tick at a time.
+pub struct Simulation {
+    pub lanes: Vec<Lane>,
+    /// One per approach.
+    pub crosswalks: Vec<Crosswalk>,
+    pub statistic: Statistics,
+    pub controller: Controller,
+    /// Simulation time in seconds.
+    pub time: f64,
+    pub settings: Rc<Settings>,
+    /// Panics with a snapshot of the tick as soon as the controller breaks a
+    /// safety invariant, see `check_invariants`. On in debug builds.
+    pub check_invariants: bool,
+    /// Draws everything random in the simulation: the speeds of the vehicles,
+    /// the pedestrians arriving and the vehicles spawned through it.
+    pub rng: StdRng,
+}
+
+impl Simulation {
+    /// A simulation seeded at random, see `with_seed`.
+    pub fn new(settings: Rc<Settings>, controller: Controller) -> Simulation {
+        Simulation::with_seed(settings, controller, rand::thread_rng().gen())
+    }
+
+    /// A simulation that runs the same way every time for the same `seed` and the
+    /// same vehicles added on the same ticks.
+    pub fn with_seed(settings: Rc<Settings>, controller: Controller, seed: usize) -> Simulation {
+        let mut statistic = Statistics::new();
+        statistic.window = (settings.warm_up, settings.warm_up + settings.measurement);
+        Simulation {
+            lanes: vec![
+                Lane::new(Cross::First, settings.clone()),
+                Lane::new(Cross::Second, settings.clone()),
+                Lane::new(Cross::Third, settings.clone()),
+                Lane::new(Cross::Fourth, settings.clone()),
+            ],
+            crosswalks: Approach::ALL.iter().map(|a| Crosswalk::new(*a, &settings)).collect(),
+            statistic,
+            controller,
+            time: 0.0,
+            settings,
+            check_invariants: cfg!(debug_assertions),
+            rng: SeedableRng::from_seed(&[seed][..]),
+        }
+    }
+
+    pub fn tick(&mut self) {
+        // pedestrians arrive at random, `pedestrian_rate` a minute on each crosswalk.
+        for crosswalk in self.crosswalks.iter_mut() {
+            if self.rng.gen::<f64>() < self.settings.pedestrian_rate / 60.0 * TICK {
+                crosswalk.add_pedestrian(self.time, &mut self.rng);
+            }
+        }
+
+        let in_use: Vec<&Crosswalk> = self.crosswalks.iter().filter(|c| c.is_in_use()).collect();
+        let areas: Vec<Rect> = in_use.iter().map(|c| c.area).collect();
+        let vehicles: Vec<Vehicle> = self.lanes
+            .iter()
+            .flat_map(|lane| lane.routes.iter())
+            .flat_map(|route| route.vehicles.iter().cloned())
+            .collect();
+        for lane in self.lanes.iter_mut() {
+            lane.update(&mut self.statistic, &areas, &vehicles, self.time, TICK, &mut self.rng);
+            for route in lane.routes.iter_mut() {
+                let leg = Approach::from(route.cross).exit_leg(route.itineraire);
+                route.exit_blocked = in_use.iter().any(|c| c.approach == leg);
+            }
+        }
+
+        // the smart road algorithm to avoid collisions
+        self.controller.control(&mut self.lanes, self.time, &mut self.statistic);
+        self.statistic.record_preemption(&self.lanes, TICK);
+        for crosswalk in self.crosswalks.iter_mut() {
+            crosswalk.control(&self.lanes, &self.settings, self.time);
+            crosswalk.update(&mut self.statistic, self.time, TICK);
+        }
+        detect_collisions(&self.lanes, &mut self.statistic);
+        if self.check_invariants {
+            if let Err(e) = check_invariants(&self.lanes, self.time) {
+                panic!("{}", e);
+            }
+        }
+
+        self.time += TICK;
+        self.statistic.sample(&self.lanes, self.time);
+    }
+
+    /// Seconds a route waits at most for the intersection, without emergency
+    /// vehicles: `Settings::max_wait`, then the platoon of a route crossing its
+    /// path drives out, and the one of a route that waited even longer and goes
+    /// first, see `Route::longest_clearing_time`.
+    pub fn wait_bound(&self) -> f64 {
+        let clearance = self.lanes
+            .iter()
+            .flat_map(|lane| lane.routes.iter())
+            .map(|route| route.longest_clearing_time())
+            .fold(0.0, f64::max);
+        self.settings.max_wait + 2.0 * clearance
+    }
+
+    /// A vehicle arriving on the approach of `cross`, see `Lane::add_vehicle`.
+    pub fn add_vehicle(&mut self, cross: Cross, itineraire: Itineraire, class: Option<VehicleClass>) {
+        if let Some(lane) = self.lanes.iter_mut().find(|lane| lane.cross == cross) {
+            lane.add_vehicle(itineraire, class, &mut self.rng);
+        }
+    }
+
+    /// A pedestrian pressing the button of a crosswalk picked at random.
+    pub fn add_pedestrian(&mut self) {
+        let i = self.rng.gen_range(0, self.crosswalks.len());
+        self.crosswalks[i].add_pedestrian(self.time, &mut self.rng);
+    }
+}
+
+/// Turns wall clock time into simulation ticks: pause, single step and time scale.
+pub struct Clock {
+    pub paused: bool,
+    scale: usize,
+    steps: usize,
+    accumulator: f64,
+}
+
+impl Clock {
+    pub fn new() -> Clock {
+        Clock {
+            paused: false,
+            scale: 2,
+            steps: 0,
+            accumulator: 0.0,
+        }
+    }
+
+    pub fn scale(&self) -> f64 {
+        SCALES[self.scale]
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        self.accumulator = 0.0;
+    }
+
+    /// Runs exactly one tick on the next frame, pausing the clock if needed.
+    pub fn step(&mut self) {
+        self.paused = true;
+        self.steps += 1;
+    }
+
+    pub fn faster(&mut self) {
+        self.scale = (self.scale + 1).min(SCALES.len() - 1);
+    }
+
+    pub fn slower(&mut self) {
+        self.scale = self.scale.saturating_sub(1);
+    }
+
+    /// Number of ticks to run for `wall_dt` seconds of wall clock.
+    pub fn ticks(&mut self, wall_dt: f64) -> usize {
+        if self.paused {
+            let steps = self.steps;
+            self.steps = 0;
+            return steps;
+        }
+
+        self.accumulator += wall_dt * self.scale() / TICK;
+        let ticks = self.accumulator.floor();
+        self.accumulator -= ticks;
+        (ticks as usize).min(MAX_TICKS_PER_FRAME)
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,236 @@
+/// Streaming summary of a series of samples.
+///
+/// Keeps min, max and mean exactly and estimates the 50th, 90th and 99th
+/// percentiles with the P² algorithm, so every `push` is O(1) in time and
+/// memory no matter how long the simulation runs.
+#[derive(Debug, Clone)]
+pub struct Aggregate {
+    pub count: usize,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    p50: Quantile,
+    p90: Quantile,
+    p99: Quantile,
+}
+
+impl Aggregate {
+    pub fn new() -> Self {
+        Self {
+            count: 0,
+            min: 0.0,
+            max: 0.0,
+            mean: 0.0,
+            p50: Quantile::new(0.5),
+            p90: Quantile::new(0.9),
+            p99: Quantile::new(0.99),
+        }
+    }
+
+    pub fn push(&mut self, value: f64) {
+        if !value.is_finite() {
+            return;
+        }
+
+        if self.count == 0 {
+            self.min = value;
+            self.max = value;
+        } else {
+            self.min = self.min.min(value);
+            self.max = self.max.max(value);
+        }
+
+        self.count += 1;
+        self.mean += (value - self.mean) / self.count as f64;
+
+        self.p50.push(value);
+        self.p90.push(value);
+        self.p99.push(value);
+    }
+
+    pub fn p50(&self) -> f64 {
+        self.p50.value()
+    }
+
+    pub fn p90(&self) -> f64 {
+        self.p90.value()
+    }
+
+    pub fn p99(&self) -> f64 {
+        self.p99.value()
+    }
+}
+
+impl Default for Aggregate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// P² estimator (Jain & Chlamtac) of a single quantile.
+///
+/// Five markers track the minimum, the maximum, the wanted quantile and the
+/// two points half way to it; their heights are moved with a piecewise
+/// parabolic formula as samples arrive.
+#[derive(Debug, Clone)]
+struct Quantile {
+    p: f64,
+    heights: [f64; 5],
+    positions: [f64; 5],
+    desired: [f64; 5],
+    increments: [f64; 5],
+    count: usize,
+}
+
+impl Quantile {
+    fn new(p: f64) -> Self {
+        Self {
+            p,
+            heights: [0.0; 5],
+            positions: [1.0, 2.0, 3.0, 4.0, 5.0],
+            desired: [1.0, 1.0 + 2.0 * p, 1.0 + 4.0 * p, 3.0 + 2.0 * p, 5.0],
+            increments: [0.0, p / 2.0, p, (1.0 + p) / 2.0, 1.0],
+            count: 0,
+        }
+    }
+
+    fn push(&mut self, value: f64) {
+        // the first five samples are kept as they come and sorted once.
+        if self.count < 5 {
+            self.heights[self.count] = value;
+            self.count += 1;
+            if self.count == 5 {
+                self.heights.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            }
+            return;
+        }
+        self.count += 1;
+
+        let k = if value < self.heights[0] {
+            self.heights[0] = value;
+            0
+        } else if value >= self.heights[4] {
+            self.heights[4] = value;
+            3
+        } else {
+            (0..4).find(|&i| value < self.heights[i + 1]).unwrap_or(3)
+        };
+
+        for i in (k + 1)..5 {
+            self.positions[i] += 1.0;
+        }
+        for i in 0..5 {
+            self.desired[i] += self.increments[i];
+        }
+
+        for i in 1..4 {
+            let d = self.desired[i] - self.positions[i];
+            if (d >= 1.0 && self.positions[i + 1] - self.positions[i] > 1.0)
+                || (d <= -1.0 && self.positions[i - 1] - self.positions[i] < -1.0)
+            {
+                let d = d.signum();
+                let height = self.parabolic(i, d);
+                self.heights[i] = if self.heights[i - 1] < height && height < self.heights[i + 1] {
+                    height
+                } else {
+                    self.linear(i, d)
+                };
+                self.positions[i] += d;
+            }
+        }
+    }
+
+    fn parabolic(&self, i: usize, d: f64) -> f64 {
+        let (q, n) = (&self.heights, &self.positions);
+        q[i] + d / (n[i + 1] - n[i - 1])
+            * ((n[i] - n[i - 1] + d) * (q[i + 1] - q[i]) / (n[i + 1] - n[i])
+                + (n[i + 1] - n[i] - d) * (q[i] - q[i - 1]) / (n[i] - n[i - 1]))
+    }
+
+    fn linear(&self, i: usize, d: f64) -> f64 {
+        let j = if d > 0.0 { i + 1 } else { i - 1 };
+        self.heights[i] + d * (self.heights[j] - self.heights[i]) / (self.positions[j] - self.positions[i])
+    }
+
+    fn value(&self) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+
+        if self.count < 5 {
+            // not enough samples for the markers yet, use the exact order statistic.
+            let mut samples = self.heights[..self.count].to_vec();
+            samples.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            let rank = (self.p * (self.count - 1) as f64).round() as usize;
+            return samples[rank];
+        }
+
+        self.heights[2]
+    }
+}
+
+/// Two sided 95% quantiles of Student's t distribution for 1 to 30 degrees of
+/// freedom, the normal one is close enough past them.
+const STUDENT_95: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228,
+    2.201, 2.179, 2.160, 2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086,
+    2.080, 2.074, 2.069, 2.064, 2.060, 2.056, 2.052, 2.048, 2.045, 2.042,
+];
+
+/// Mean of a few independent samples, such as one measure over several runs,
+/// with the half width of its 95% confidence interval.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Estimate {
+    pub mean: f64,
+    pub half_width: f64,
+}
+
+impl Estimate {
+    /// A single sample, or none, gives an interval of unknown width: infinite.
+    pub fn of(samples: &[f64]) -> Self {
+        let n = samples.len();
+        if n == 0 {
+            return Self { mean: 0.0, half_width: f64::INFINITY };
+        }
+
+        let mean = samples.iter().sum::<f64>() / n as f64;
+        if n == 1 {
+            return Self { mean, half_width: f64::INFINITY };
+        }
+
+        let variance = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1) as f64;
+        let t = STUDENT_95.get(n - 2).copied().unwrap_or(1.96);
+        Self { mean, half_width: t * (variance / n as f64).sqrt() }
+    }
+}
+
+/// Fixed width histogram growing as larger values come in, the last bin
+/// collects everything above `MAX_BINS` bins.
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    pub bin_width: f64,
+    pub counts: Vec<usize>,
+}
+
+const MAX_BINS: usize = 200;
+
+impl Histogram {
+    pub fn new(bin_width: f64) -> Self {
+        Self {
+            bin_width,
+            counts: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, value: f64) {
+        if !value.is_finite() || value < 0.0 {
+            return;
+        }
+
+        let bin = ((value / self.bin_width) as usize).min(MAX_BINS - 1);
+        if bin >= self.counts.len() {
+            self.counts.resize(bin + 1, 0);
+        }
+        self.counts[bin] += 1;
+    }
+}
@@ -0,0 +1,167 @@
+use rand::Rng;
+use sdl2::pixels::Color;
+
+/// The kinds of vehicles driving through the intersection.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum VehicleClass {
+    Car,
+    Bus,
+    Truck,
+    Motorbike,
+    /// Given a clear path by the controller, see `preempt_for_emergency`.
+    Emergency,
+}
+
+/// Everything that depends on the class of a vehicle, sizes are in multiples
+/// of `Settings::vehicle` and speeds in pixels per tick.
+#[derive(Debug, Clone)]
+pub struct ClassSpec {
+    pub class: VehicleClass,
+    /// Along the direction of travel.
+    pub length: f64,
+    /// Across the direction of travel, it must fit in a lane.
+    pub width: f64,
+    /// The `Vilosity` levels: Reduce, Slow, Medium and Fast.
+    pub speeds: [f32; 4],
+    /// Speed gained per tick, braking is always immediate.
+    pub acceleration: f32,
+    /// Radius of the turns, the tighter the slower the turn is taken.
+    pub turning_radius: f64,
+    /// Sprite and tint the class is drawn with, `None` picks one of the car sprites.
+    pub sprite: Option<(&'static str, Color)>,
+}
+
+/// Lateral acceleration, in pixels per tick squared, no vehicle turns faster than.
+const LATERAL_GRIP: f64 = 0.2;
+
+pub const CLASSES: [ClassSpec; 5] = [
+    ClassSpec {
+        class: VehicleClass::Car,
+        length: 1.0,
+        width: 1.0,
+        speeds: [0.1, 0.5, 2.0, 3.0],
+        acceleration: 0.5,
+        turning_radius: 1.6,
+        sprite: None,
+    },
+    ClassSpec {
+        class: VehicleClass::Bus,
+        length: 2.5,
+        width: 1.0,
+        speeds: [0.1, 0.4, 1.5, 2.2],
+        acceleration: 0.05,
+        turning_radius: 3.0,
+        sprite: Some(("WhiteStrip.png", Color::RGB(255, 200, 40))),
+    },
+    ClassSpec {
+        class: VehicleClass::Truck,
+        length: 2.0,
+        width: 1.0,
+        speeds: [0.1, 0.4, 1.3, 2.0],
+        acceleration: 0.04,
+        turning_radius: 2.5,
+        sprite: Some(("WhiteStrip.png", Color::RGB(200, 120, 70))),
+    },
+    ClassSpec {
+        class: VehicleClass::Motorbike,
+        length: 0.7,
+        width: 0.5,
+        speeds: [0.1, 0.6, 2.4, 3.5],
+        acceleration: 0.8,
+        turning_radius: 1.0,
+        sprite: Some(("RedStrip.png", Color::RGB(255, 255, 255))),
+    },
+    ClassSpec {
+        class: VehicleClass::Emergency,
+        length: 1.4,
+        width: 1.0,
+        speeds: [0.1, 0.6, 2.6, 3.6],
+        acceleration: 0.6,
+        turning_radius: 1.5,
+        sprite: Some(("WhiteStrip.png", Color::RGB(255, 60, 60))),
+    },
+];
+
+/// Number of car sprites, the sprites of the other classes come after them.
+pub const CAR_SPRITES: usize = 6;
+
+impl VehicleClass {
+    pub const ALL: [VehicleClass; 5] = [
+        VehicleClass::Car,
+        VehicleClass::Bus,
+        VehicleClass::Truck,
+        VehicleClass::Motorbike,
+        VehicleClass::Emergency,
+    ];
+
+    pub fn spec(&self) -> &'static ClassSpec {
+        CLASSES.iter().find(|s| s.class == *self).unwrap()
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            VehicleClass::Car => "car",
+            VehicleClass::Bus => "bus",
+            VehicleClass::Truck => "truck",
+            VehicleClass::Motorbike => "motorbike",
+            VehicleClass::Emergency => "emergency",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<VehicleClass> {
+        VehicleClass::ALL.into_iter().find(|c| c.name() == name)
+    }
+
+    /// Index of the sprite in the textures loaded by `cars_texture`, `rng` draws
+    /// one of the car sprites.
+    pub fn texture<R: Rng>(&self, rng: &mut R) -> usize {
+        match self.spec().sprite {
+            None => rng.gen_range(0, CAR_SPRITES),
+            Some(_) => {
+                let i = CLASSES.iter().filter(|s| s.sprite.is_some()).position(|s| s.class == *self).unwrap();
+                CAR_SPRITES + i
+            }
+        }
+    }
+
+    /// Highest speed, in pixels per tick, a turn of the class is taken at.
+    pub fn turn_speed(&self, vehicle: i32) -> f32 {
+        (LATERAL_GRIP * self.spec().turning_radius * vehicle as f64).sqrt() as f32
+    }
+
+    /// Parses a spawn mix written as `car=70,bus=10,truck=10,motorbike=10`.
+    pub fn parse_mix(text: &str) -> Result<Vec<(VehicleClass, u32)>, String> {
+        let mix = text
+            .split(',')
+            .map(|entry| {
+                let (name, weight) = entry.split_once('=').ok_or_else(|| format!("`{}` is not class=weight", entry))?;
+                let class = VehicleClass::from_name(name.trim()).ok_or_else(|| format!("unknown vehicle class `{}`", name))?;
+                let weight = weight.trim().parse().map_err(|_| format!("`{}` is not a weight", weight))?;
+                Ok((class, weight))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        if mix.iter().all(|(_, weight)| *weight == 0) {
+            return Err(String::from("the spawn mix has no weight"));
+        }
+        Ok(mix)
+    }
+
+    /// Picks a class drawn from `rng`, each one as often as its weight in `mix`:
+    /// a seeded `rng` repeats the same classes.
+    pub fn pick_with<R: Rng>(mix: &[(VehicleClass, u32)], rng: &mut R) -> VehicleClass {
+        let total: u32 = mix.iter().map(|(_, weight)| weight).sum();
+        if total == 0 {
+            return VehicleClass::Car;
+        }
+
+        let mut n = rng.gen_range(0, total);
+        for (class, weight) in mix {
+            if n < *weight {
+                return *class;
+            }
+            n -= weight;
+        }
+        VehicleClass::Car
+    }
+}
@@ -0,0 +1,39 @@
+use smart_road::*;
+
+mod common;
+
+/// A car going straight from the North, and one going straight from the West
+/// `delay` ticks later while the first holds the intersection, the simulation
+/// seeded by `seed`.
+fn crossing_paths(speed_advisory: bool, delay: usize, seed: usize) -> Statistics {
+    let mut settings = common::settings();
+    settings.speed_advisory = speed_advisory;
+    let mut sim = common::simulation(settings, Controller::SmartIntersection, seed);
+
+    let mut slot = false;
+    let arrive = |tick: usize, sim: &mut Simulation| {
+        if tick == 0 {
+            sim.lanes[0].add_vehicle(Itineraire::Straight, Some(VehicleClass::Car), &mut sim.rng);
+        }
+        if tick == delay {
+            sim.lanes[1].add_vehicle(Itineraire::Straight, Some(VehicleClass::Car), &mut sim.rng);
+        }
+    };
+    common::drive(&mut sim, 30, arrive, |sim| slot |= sim.lanes[1].routes.iter().any(|route| route.slot.is_some()));
+    assert_eq!(slot, speed_advisory);
+    assert_eq!(sim.statistic.max_vehicles_passed, 2);
+    sim.statistic
+}
+
+#[test]
+fn the_second_car_times_its_arrival_instead_of_stopping() {
+    // the same seed draws the same speeds: the car advised without stopping is
+    // the one stopping without the advisory.
+    for seed in 0..4 {
+        let advised = crossing_paths(true, 20, seed);
+        assert_eq!((advised.stops, advised.stops_avoided), (0, 1), "seed {}", seed);
+
+        let unadvised = crossing_paths(false, 20, seed);
+        assert_eq!((unadvised.stops, unadvised.stops_avoided), (1, 0), "seed {}", seed);
+    }
+}
@@ -0,0 +1,211 @@
+use rand::{SeedableRng, StdRng};
+use smart_road::sdl2::rect::Point;
+use smart_road::*;
+
+mod common;
+
+const CASES: usize = 200;
+const ITINERAIRES: [Itineraire; 3] = [Itineraire::Left, Itineraire::Straight, Itineraire::Right];
+
+fn for_random_settings(seed: usize, check: impl Fn(&Settings)) {
+    let mut rng: StdRng = SeedableRng::from_seed(&[seed][..]);
+    for _ in 0..CASES {
+        check(&common::random_settings(&mut rng));
+    }
+}
+
+fn along(p: Point, travel: (i32, i32)) -> i32 {
+    p.x * travel.0 + p.y * travel.1
+}
+
+#[test]
+fn conversions_round_trip() {
+    for approach in Approach::ALL {
+        assert_eq!(Approach::from(approach.cross()), approach);
+        assert_eq!(Approach::from(Cross::from(approach)), approach);
+        assert_eq!(Approach::from_direction(approach.direction()), Some(approach));
+        assert_eq!(Approach::from_key(approach.spec().key), Some(approach));
+        assert_eq!(approach.cross().direction(), approach.direction());
+        assert_eq!(approach.cross().name(), approach.name());
+        assert_eq!(approach.opposite().opposite(), approach);
+        assert_ne!(approach.opposite(), approach);
+    }
+    assert_eq!(Approach::from_direction(Direction::None), None);
+}
+
+#[test]
+fn table_has_one_row_per_approach() {
+    for (i, a) in APPROACHES.iter().enumerate() {
+        for b in APPROACHES.iter().skip(i + 1) {
+            assert_ne!(a.approach, b.approach);
+            assert_ne!(a.cross, b.cross);
+            assert_ne!(a.direction, b.direction);
+            assert_ne!(a.key, b.key);
+            assert_ne!(a.travel, b.travel);
+        }
+
+        let (dx, dy) = a.travel;
+        assert_eq!(dx.abs() + dy.abs(), 1, "{:?} travel is not a unit vector", a.approach);
+        for itineraire in ITINERAIRES {
+            assert_eq!(a.lanes.iter().filter(|(i, _)| *i == itineraire).count(), 1);
+        }
+    }
+}
+
+#[test]
+fn turns_are_consistent() {
+    for approach in Approach::ALL {
+        assert_eq!(approach.exit(Itineraire::Straight), approach);
+        assert_eq!(approach.exit(Itineraire::Left).exit(Itineraire::Right), approach);
+        assert_eq!(approach.exit(Itineraire::Left).exit(Itineraire::Left), approach.opposite());
+        assert_eq!(approach.exit(Itineraire::Right).exit(Itineraire::Right), approach.opposite());
+
+        // vehicles drive on the right: coming from the North a left turn heads East.
+        let (dx, dy) = approach.spec().travel;
+        let (lx, ly) = approach.exit(Itineraire::Left).spec().travel;
+        assert_eq!(dx * ly - dy * lx, -1, "{:?} left turn goes the wrong way", approach);
+    }
+    assert_eq!(Approach::North.exit(Itineraire::Left), Approach::West);
+    assert_eq!(Approach::North.exit(Itineraire::Left).spec().direction, Direction::Right);
+}
+
+#[test]
+fn spawn_points_are_on_the_entry_edge_and_right_hand_side() {
+    for_random_settings(1, |settings| {
+        let center = Point::new(settings.width / 2, settings.height / 2);
+        for approach in Approach::ALL {
+            let travel = approach.spec().travel;
+            let mut sides = Vec::new();
+            for itineraire in ITINERAIRES {
+                let spawn = approach.spawn(itineraire, settings);
+                match approach {
+                    Approach::North => assert_eq!(spawn.y, 0),
+                    Approach::South => assert_eq!(spawn.y, settings.height),
+                    Approach::West => assert_eq!(spawn.x, 0),
+                    Approach::East => assert_eq!(spawn.x, settings.width),
+                }
+
+                // the right hand side of the travel direction is (-dy, dx).
+                let side = (spawn.x - center.x) * -travel.1 + (spawn.y - center.y) * travel.0;
+                assert!(side > 0, "{:?} {:?} spawns on the wrong side", approach, itineraire);
+                sides.push(side);
+            }
+
+            // from the inner lane to the outer one: Left, Straight, Right.
+            assert!(sides[0] < sides[1] && sides[1] < sides[2], "{:?} lanes are out of order", approach);
+        }
+    });
+}
+
+#[test]
+fn stop_line_is_between_spawn_and_center() {
+    for_random_settings(2, |settings| {
+        let center = Point::new(settings.width / 2, settings.height / 2);
+        for approach in Approach::ALL {
+            let travel = approach.spec().travel;
+            let stop = approach.stop_point(settings);
+            let stop_along = if travel.0 == 0 { stop.y * travel.1 } else { stop.x * travel.0 };
+            for itineraire in ITINERAIRES {
+                let spawn = approach.spawn(itineraire, settings);
+                assert!(along(spawn, travel) < stop_along);
+                assert!(stop_along < along(center, travel));
+            }
+        }
+    });
+}
+
+#[test]
+fn lanes_use_the_table() {
+    for_random_settings(3, |settings| {
+        let settings = Rc::new(settings.clone());
+        for approach in Approach::ALL {
+            let lane = Lane::new(approach.cross(), settings.clone());
+            assert_eq!(lane.stop_point, approach.stop_point(&settings));
+
+            for route in lane.routes.iter() {
+                assert_eq!(route.stop_point, lane.stop_point);
+                let path = route.path();
+                assert_eq!(path[0], approach.spawn(route.itineraire, &settings));
+
+                // the route goes in the travel direction first, then the exit one.
+                let travel = approach.spec().travel;
+                assert!(along(path[1], travel) > along(path[0], travel));
+                let exit = approach.exit(route.itineraire).spec().travel;
+                let last = path.len() - 1;
+                assert!(along(path[last], exit) > along(path[last - 1], exit));
+
+                // the stop line sits on the first segment of the path.
+                let anchor = route.stop_anchor();
+                assert!(along(anchor, travel) >= along(path[0], travel));
+                assert!(along(anchor, travel) <= along(path[1], travel));
+            }
+        }
+    });
+}
+
+#[test]
+fn spawned_vehicles_follow_the_table() {
+    let settings = Rc::new(common::default_settings());
+    for approach in Approach::ALL {
+        for itineraire in ITINERAIRES {
+            let mut vehicle = Vehicle::new(approach.direction(), itineraire, settings.clone(), &mut rand::thread_rng());
+            vehicle.spawn(approach.direction());
+            assert_eq!(vehicle.position, approach.spawn(itineraire, &settings));
+            assert_eq!(vehicle.heading(), approach.spec().angle);
+
+            let travel = vehicle.travel_direction();
+            assert_eq!((travel.x, travel.y), approach.spec().travel);
+
+            // drive the vehicle across the map, it must turn where the table says.
+            let turn = approach.turn_point(itineraire, &settings);
+            for _ in 0..2000 {
+                if vehicle.has_reached_end() {
+                    break;
+                }
+                vehicle.update(TICK);
+            }
+            assert!(vehicle.has_reached_end(), "{:?} {:?} never left the map", approach, itineraire);
+            assert_eq!(vehicle.is_changed_direction, turn.is_some());
+
+            let exit = approach.exit(itineraire);
+            let travel = vehicle.travel_direction();
+            assert_eq!((travel.x, travel.y), exit.spec().travel);
+            assert_eq!(vehicle.heading(), exit.spec().angle);
+        }
+    }
+}
+
+#[test]
+fn u_turns_head_back_on_the_inner_lane() {
+    let settings = Rc::new(common::default_settings());
+    for approach in Approach::ALL {
+        assert_eq!(approach.exit(Itineraire::UTurn), approach.opposite());
+        assert_eq!(approach.exit_leg(Itineraire::UTurn), approach);
+        assert_eq!(approach.spawn(Itineraire::UTurn, &settings), approach.spawn(Itineraire::Left, &settings));
+
+        // in, across the intersection the way of a left turn, and back out.
+        let path = approach.path(Itineraire::UTurn, &settings);
+        assert_eq!(path.len(), 4);
+        let travel = approach.spec().travel;
+        let left = approach.exit(Itineraire::Left).spec().travel;
+        let back = approach.opposite().spec().travel;
+        assert!(along(path[1], travel) > along(path[0], travel));
+        assert!(along(path[2], left) > along(path[1], left));
+        assert!(along(path[3], back) > along(path[2], back));
+
+        let mut vehicle = Vehicle::with_class(approach.direction(), Itineraire::UTurn, VehicleClass::Car, settings.clone(), &mut rand::thread_rng());
+        vehicle.spawn(approach.direction());
+        for _ in 0..2000 {
+            if vehicle.has_reached_end() {
+                break;
+            }
+            vehicle.update(TICK);
+        }
+        assert!(vehicle.has_reached_end(), "{:?} U-turn never left the map", approach);
+        assert!(vehicle.is_turned_back);
+        assert_eq!(vehicle.heading(), approach.opposite().spec().angle);
+        let travel = vehicle.travel_direction();
+        assert_eq!((travel.x, travel.y), back);
+        assert!((vehicle.position.x - path[3].x).abs() + (vehicle.position.y - path[3].y).abs() < 2 * settings.vehicle + 5);
+    }
+}
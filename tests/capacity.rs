@@ -0,0 +1,36 @@
+use smart_road::*;
+
+mod common;
+
+#[test]
+fn a_movement_saturates_below_the_end_of_the_ramp() {
+    let settings = Rc::new(common::default_settings());
+    let analysis = CapacityAnalysis {
+        controller: Controller::SmartIntersection,
+        background: 5.0,
+        volumes: CapacityAnalysis::parse_volumes("north-straight=8, West-Left=2").unwrap(),
+        step: 20.0,
+        max_demand: 200.0,
+        duration: 30.0,
+        seed: 1,
+    };
+    let simulation = Simulation::new(settings.clone(), Controller::SmartIntersection);
+    let routes: Vec<(Cross, Itineraire)> = simulation.lanes.iter().flat_map(|lane| lane.routes.iter()).map(|r| (r.cross, r.itineraire)).collect();
+    assert_eq!(CapacityAnalysis::MOVEMENTS.to_vec(), routes);
+
+    let capacity = analysis.movement(&settings, (Cross::First, Itineraire::Straight));
+    assert!(capacity.saturated_at.is_some());
+    assert!(capacity.capacity > 0.0);
+    assert!(capacity.saturation_flow >= capacity.capacity);
+    assert_eq!(capacity.volume, 8.0);
+    assert!((capacity.volume_capacity - 8.0 / capacity.capacity).abs() < 1e-9);
+    assert_eq!(analysis.volume((Cross::Second, Itineraire::Left)), 2.0);
+    assert_eq!(analysis.volume((Cross::Second, Itineraire::Right)), 5.0);
+    assert!(CapacityAnalysis::parse_volumes("north-sideways=1").is_err());
+
+    let table = capacity_table(&[capacity]);
+    assert_eq!(table.lines().count(), 4);
+    let last = table.lines().last().unwrap();
+    assert!(last.starts_with("| intersection, critical |"));
+    assert!(last.ends_with(&format!("| {:.2} |", capacity.volume_capacity)));
+}
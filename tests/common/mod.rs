@@ -0,0 +1,55 @@
+//! The fixtures shared by the tests.
+#![allow(dead_code)]
+
+use rand::{Rng, StdRng};
+use smart_road::*;
+
+/// The settings of the binaries.
+pub fn default_settings() -> Settings {
+    Settings::new(1000, 1000, 30, 1, 100.0)
+}
+
+/// The settings of the binaries without the pedestrians arriving on their own,
+/// who would hold the routes of the vehicles a test places.
+pub fn settings() -> Settings {
+    let mut settings = default_settings();
+    settings.pedestrian_rate = 0.0;
+    settings
+}
+
+/// A simulation of `settings` under `controller`, seeded by `seed`.
+pub fn simulation(settings: Settings, controller: Controller, seed: usize) -> Simulation {
+    Simulation::with_seed(Rc::new(settings), controller, seed)
+}
+
+/// Random but valid map dimensions, the key points must stay inside the map.
+pub fn random_settings(rng: &mut StdRng) -> Settings {
+    Settings::new(
+        rng.gen_range(700, 1400),
+        rng.gen_range(700, 1400),
+        rng.gen_range(10, 31),
+        rng.gen_range(0, 5),
+        rng.gen_range(50, 150) as f64,
+    )
+}
+
+/// Every route of `lanes`, in their order.
+pub fn routes(lanes: &mut [Lane]) -> Vec<&mut Route> {
+    lanes.iter_mut().flat_map(|lane| lane.routes.iter_mut()).collect()
+}
+
+/// The route `(cross, itineraire)` of `lanes`.
+pub fn route(lanes: &mut [Lane], cross: Cross, itineraire: Itineraire) -> &mut Route {
+    routes(lanes).into_iter().find(|r| (r.cross, r.itineraire) == (cross, itineraire)).unwrap()
+}
+
+/// Simulates `seconds` of a scenario: `arrive` brings what arrives on each tick
+/// before it and `observe` looks at the simulation after it. Nothing collides.
+pub fn drive(simulation: &mut Simulation, seconds: usize, mut arrive: impl FnMut(usize, &mut Simulation), mut observe: impl FnMut(&Simulation)) {
+    for tick in 0..seconds * 60 {
+        arrive(tick, simulation);
+        simulation.tick();
+        observe(simulation);
+    }
+    assert_eq!(simulation.statistic.collisions, 0);
+}
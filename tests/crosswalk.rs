@@ -0,0 +1,116 @@
+use smart_road::*;
+
+mod common;
+
+fn crosswalk(simulation: &Simulation, approach: Approach) -> usize {
+    simulation.crosswalks.iter().position(|c| c.approach == approach).unwrap()
+}
+
+/// Keeps the crosswalk of `approach` in use: someone is always walking on it.
+fn walk(simulation: &mut Simulation, approach: Approach) {
+    let (i, time) = (crosswalk(simulation, approach), simulation.time);
+    let crosswalk = &mut simulation.crosswalks[i];
+    if crosswalk.pedestrians.is_empty() {
+        crosswalk.add_pedestrian(time, &mut simulation.rng);
+    }
+    crosswalk.walk_since = Some(time);
+}
+
+#[test]
+fn a_walk_phase_waits_for_a_gap_in_the_traffic_or_the_longest_wait() {
+    let mut simulation = common::simulation(common::settings(), Controller::SmartIntersection, 0);
+    let settings = simulation.settings.clone();
+    let mut crosswalk = Crosswalk::new(Approach::North, &settings);
+    crosswalk.add_pedestrian(0.0, &mut simulation.rng);
+    crosswalk.control(&simulation.lanes, &settings, 0.0);
+    assert!(crosswalk.is_in_use(), "nobody around, the pedestrian goes at once");
+
+    // a car coming at full speed up to the crosswalk.
+    let route = common::route(&mut simulation.lanes, Cross::First, Itineraire::Straight);
+    route.add_vehicle_of_class(Cross::First.direction(), VehicleClass::Car, &mut simulation.rng);
+    let car = &mut route.vehicles[0];
+    car.position.y = crosswalk.area.top() - settings.vehicle - 2;
+    car.velocity = car.velosity_type[3];
+    assert!(!car.bounding_box().has_intersection(crosswalk.area));
+
+    let mut crosswalk = Crosswalk::new(Approach::North, &settings);
+    crosswalk.add_pedestrian(0.0, &mut simulation.rng);
+    crosswalk.control(&simulation.lanes, &settings, MAX_PEDESTRIAN_WAIT - 1.0);
+    assert!(!crosswalk.is_in_use(), "the pedestrian went in front of the car");
+    crosswalk.control(&simulation.lanes, &settings, MAX_PEDESTRIAN_WAIT + 1.0);
+    assert!(crosswalk.is_in_use(), "the pedestrian waited past the longest wait");
+
+    // on the crosswalk, the car has to be off it first however long they waited.
+    let car = &mut common::route(&mut simulation.lanes, Cross::First, Itineraire::Straight).vehicles[0];
+    car.position.y = crosswalk.area.top();
+    let mut crosswalk = Crosswalk::new(Approach::North, &settings);
+    crosswalk.add_pedestrian(0.0, &mut simulation.rng);
+    crosswalk.control(&simulation.lanes, &settings, MAX_PEDESTRIAN_WAIT + 1.0);
+    assert!(!crosswalk.is_in_use());
+}
+
+#[test]
+fn a_vehicle_stops_short_of_a_crosswalk_in_use() {
+    let mut simulation = common::simulation(common::settings(), Controller::SmartIntersection, 0);
+    simulation.check_invariants = false;
+    let area = simulation.crosswalks[crosswalk(&simulation, Approach::North)].area;
+    let route = common::route(&mut simulation.lanes, Cross::First, Itineraire::Straight);
+    route.add_vehicle_of_class(Cross::First.direction(), VehicleClass::Car, &mut simulation.rng);
+
+    // given the intersection, the car still waits for the pedestrians.
+    let arrive = |_: usize, simulation: &mut Simulation| {
+        walk(simulation, Approach::North);
+        common::route(&mut simulation.lanes, Cross::First, Itineraire::Straight).stage = Stage::Crossing;
+    };
+    common::drive(&mut simulation, 10, arrive, |simulation| {
+        let car = &simulation.lanes[0].routes.iter().find(|r| r.itineraire == Itineraire::Straight).unwrap().vehicles[0];
+        assert!(!car.bounding_box().has_intersection(area), "{:?} drove onto {:?}", car.bounding_box(), area);
+    });
+    let car = &common::route(&mut simulation.lanes, Cross::First, Itineraire::Straight).vehicles[0];
+    assert_eq!((car.velocity, car.stage), (0.0, Stage::Waiting));
+
+    // and goes once they are across.
+    common::drive(&mut simulation, 20, |_, _| {}, |_| {});
+    assert_eq!(simulation.statistic.max_vehicles_passed, 1);
+}
+
+#[test]
+fn a_route_leaving_by_a_crosswalk_in_use_is_held() {
+    let mut simulation = common::simulation(common::settings(), Controller::SmartIntersection, 0);
+    let from = Approach::North.opposite().cross();
+    let route = common::route(&mut simulation.lanes, from, Itineraire::Straight);
+    route.add_vehicle_of_class(from.direction(), VehicleClass::Car, &mut simulation.rng);
+
+    common::drive(&mut simulation, 10, |_, simulation| walk(simulation, Approach::North), |simulation| {
+        for route in simulation.lanes.iter().flat_map(|lane| lane.routes.iter()) {
+            let leaving = Approach::from(route.cross).exit_leg(route.itineraire) == Approach::North;
+            assert_eq!(route.exit_blocked, leaving, "{:?} {:?}", route.cross, route.itineraire);
+            if leaving {
+                assert!(route.is_held());
+                assert!(route.stage != Stage::Crossing && route.vehicles.iter().all(|v| v.stage == Stage::Waiting));
+            }
+        }
+    });
+
+    common::drive(&mut simulation, 20, |_, _| {}, |_| {});
+    assert_eq!(simulation.statistic.max_vehicles_passed, 1);
+}
+
+#[test]
+fn the_wait_and_the_crossing_of_the_pedestrians_are_recorded() {
+    let mut simulation = common::simulation(common::settings(), Controller::SmartIntersection, 0);
+    let arrive = |tick: usize, simulation: &mut Simulation| {
+        if tick == 0 {
+            let (i, time) = (crosswalk(simulation, Approach::West), simulation.time);
+            simulation.crosswalks[i].add_pedestrian(time, &mut simulation.rng);
+        }
+    };
+    common::drive(&mut simulation, 20, arrive, |_| {});
+
+    let statistic = &simulation.statistic;
+    assert_eq!(statistic.pedestrians_crossed, 1);
+    assert_eq!(statistic.pedestrian_wait.count, 1);
+    // nothing in the way, they went on the next tick.
+    assert!(statistic.pedestrian_wait.max <= TICK, "{}", statistic.pedestrian_wait.max);
+    assert!(simulation.crosswalks.iter().all(|c| c.pedestrians.is_empty() && !c.is_in_use()));
+}
@@ -0,0 +1,104 @@
+use smart_road::*;
+
+mod common;
+
+fn environment() -> Environment {
+    Environment::new(Rc::new(common::default_settings()), 20.0, 60.0, 1.0)
+}
+
+/// Grants the routes with a queue in order, skipping the ones whose paths cross
+/// a route crossing or granted already.
+fn greedy(observation: &Observation) -> Action {
+    let mut grants: Vec<(Cross, Itineraire)> = observation.routes
+        .iter()
+        .filter(|r| r.stage == Stage::Crossing)
+        .map(|r| r.route)
+        .collect();
+    for route in observation.routes.iter().filter(|r| r.stage != Stage::Crossing && r.queue > 0) {
+        if !grants.iter().any(|g| conflicts(route.route, *g)) {
+            grants.push(route.route);
+        }
+    }
+    Action { grants }
+}
+
+#[test]
+fn an_agent_granting_nothing_lets_no_vehicle_through() {
+    let mut environment = environment();
+    let observation = environment.reset(1);
+    assert_eq!(observation.routes.len(), 16);
+    assert!(observation.routes.iter().all(|r| r.queue == 0 && r.stage == Stage::Waiting));
+
+    let mut steps = 0;
+    let mut total = 0.0;
+    loop {
+        let (observation, reward, done) = environment.step(&Action::default());
+        steps += 1;
+        total += reward;
+        assert!(reward <= 0.0);
+        assert!(observation.routes.iter().all(|r| r.queue == r.distances.len()));
+        if done {
+            break;
+        }
+    }
+    assert_eq!(steps, 60);
+    assert!(total < 0.0);
+    assert_eq!(environment.simulation.statistic.max_vehicles_passed, 0);
+}
+
+#[test]
+fn a_greedy_agent_gets_the_vehicles_through_without_collisions() {
+    let mut environment = environment();
+    let mut observation = environment.reset(1);
+    let mut idle = 0.0;
+    loop {
+        let (next, reward, done) = environment.step(&greedy(&observation));
+        observation = next;
+        idle += reward;
+        if done {
+            break;
+        }
+    }
+
+    let mut blocked = self::environment();
+    blocked.reset(1);
+    let mut waited = 0.0;
+    for _ in 0..60 {
+        waited += blocked.step(&Action::default()).1;
+    }
+    assert!(environment.simulation.statistic.max_vehicles_passed > 0);
+    assert_eq!(environment.simulation.statistic.collisions, 0);
+    assert!(idle > waited, "{} against {}", idle, waited);
+}
+
+#[test]
+fn the_protocol_answers_a_line_per_request() {
+    let mut environment = environment();
+    let reset = environment.respond(r#"{"op":"reset","seed":2}"#);
+    assert_eq!(reset.field("observation").unwrap().field("routes").unwrap().as_array().unwrap().len(), 16);
+
+    let step = environment.respond(r#"{"op":"step","grants":[{"cross":"First","itineraire":"Left"}]}"#);
+    assert_eq!(step.field("done").unwrap().as_bool(), Some(false));
+    assert!(step.field("reward").unwrap().as_f64().is_some());
+    assert!((step.field("observation").unwrap().field("t").unwrap().as_f64().unwrap() - 1.0).abs() < 1e-6);
+
+    assert!(environment.respond(r#"{"op":"step","grants":[{"cross":"Fifth"}]}"#).get("error").is_some());
+    assert!(environment.respond(r#"{"op":"jump"}"#).get("error").is_some());
+    assert!(environment.respond("not json").get("error").is_some());
+}
+
+#[test]
+fn a_seed_and_the_same_actions_replay_an_episode() {
+    let episode = |seed: usize| {
+        let mut environment = environment();
+        let mut observation = environment.reset(seed);
+        let mut steps = Vec::new();
+        for _ in 0..30 {
+            let (next, reward, _) = environment.step(&greedy(&observation));
+            steps.push((next.clone(), reward));
+            observation = next;
+        }
+        steps
+    };
+    assert_eq!(episode(6), episode(6));
+}
@@ -0,0 +1,67 @@
+use smart_road::*;
+
+mod common;
+
+#[test]
+fn estimates_use_the_student_interval() {
+    let estimate = Estimate::of(&[1.0, 2.0, 3.0]);
+    assert_eq!(estimate.mean, 2.0);
+    // s = 1 on 3 samples: t(2) / sqrt(3).
+    assert!((estimate.half_width - 4.303 / 3f64.sqrt()).abs() < 1e-9);
+
+    assert_eq!(Estimate::of(&[5.0; 40]).half_width, 0.0);
+    assert!(Estimate::of(&[5.0]).half_width.is_infinite());
+}
+
+#[test]
+fn the_table_has_a_row_per_experiment() {
+    let settings = Rc::new(common::default_settings());
+    let results: Vec<(Experiment, Vec<Run>)> = [10.0, 40.0]
+        .iter()
+        .map(|demand| {
+            let experiment = Experiment { controller: Controller::SmartIntersection, demand: *demand, duration: 20.0 };
+            (experiment, (0..2).map(|seed| experiment.run(&settings, seed)).collect())
+        })
+        .collect();
+
+    for (_, runs) in results.iter() {
+        assert!(runs.iter().all(|run| run.statistic.collisions == 0));
+    }
+
+    let table = summary_table(&results);
+    let lines: Vec<&str> = table.lines().collect();
+    assert_eq!(lines.len(), 2 + results.len());
+    for measure in Measure::ALL.iter() {
+        assert!(lines[0].contains(measure.name));
+    }
+    assert!(lines[2].starts_with("| smart intersection | 10 "));
+    assert!(lines[3].contains(" ± "));
+}
+
+#[test]
+fn only_the_vehicles_within_the_window_are_measured() {
+    let mut settings = common::default_settings();
+    settings.warm_up = 20.0;
+    settings.measurement = 40.0;
+    let settings = Rc::new(settings);
+
+    let experiment = Experiment { controller: Controller::SmartIntersection, demand: 10.0, duration: 90.0 };
+    let run = experiment.run(&settings, 3);
+    assert_eq!(run.duration, 40.0);
+    assert!(run.statistic.max_vehicles_passed > 0);
+    // in through the window's start and out before its end.
+    assert!(run.statistic.time_to_pass.max <= 40.0);
+    assert_eq!(run.statistic.time_to_pass.count, run.statistic.max_vehicles_passed);
+}
+
+#[test]
+fn a_seed_always_gives_the_same_run() {
+    // with the default pedestrians, drawn by the simulation too.
+    let settings = Rc::new(common::default_settings());
+    let experiment = Experiment { controller: Controller::SmartIntersection, demand: 20.0, duration: 60.0 };
+    let rows = |seed: usize| -> Vec<f64> {
+        let run = experiment.run(&settings, seed);
+        Measure::ALL.iter().map(|m| (m.read)(&run)).chain([run.statistic.pedestrians_crossed as f64]).collect()
+    };
+    assert_eq!(rows(4), rows(4));
+}
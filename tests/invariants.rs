@@ -0,0 +1,116 @@
+use smart_road::*;
+
+mod common;
+
+fn simulation() -> Simulation {
+    let mut simulation = common::simulation(common::settings(), Controller::SmartIntersection, 0);
+    simulation.check_invariants = false;
+    simulation
+}
+
+/// The snapshot at the end of the error is a frame the replay reads back.
+fn snapshot(error: &str) -> Frame {
+    let json = Json::parse(error.lines().last().unwrap()).unwrap();
+    Frame::from_json(&json).unwrap()
+}
+
+#[test]
+fn the_controllers_keep_the_invariants_under_mixed_demand() {
+    let mut settings = common::default_settings();
+    settings.pedestrian_rate = 4.0;
+    settings.mix.push((VehicleClass::Emergency, 5));
+    let settings = Rc::new(settings);
+
+    let itineraires = [Itineraire::Left, Itineraire::Straight, Itineraire::Right, Itineraire::UTurn];
+    for controller in Controller::ALL {
+        let mut simulation = Simulation::with_seed(settings.clone(), controller, 0);
+        simulation.check_invariants = true;
+        for tick in 0..60 * 60 {
+            if tick % 15 == 0 {
+                for lane in simulation.lanes.iter_mut() {
+                    lane.add_vehicle(itineraires[tick / 15 % itineraires.len()], None, &mut simulation.rng);
+                }
+            }
+            simulation.tick();
+        }
+    }
+}
+
+#[test]
+fn conflicting_routes_crossing_together_are_reported() {
+    let mut simulation = simulation();
+    for cross in [Cross::First, Cross::Second] {
+        common::route(&mut simulation.lanes, cross, Itineraire::Straight).add_vehicle_of_class(cross.direction(), VehicleClass::Car, &mut simulation.rng);
+    }
+
+    let mut error = None;
+    for _ in 0..60 * 10 {
+        for cross in [Cross::First, Cross::Second] {
+            common::route(&mut simulation.lanes, cross, Itineraire::Straight).stage = Stage::Crossing;
+        }
+        simulation.tick();
+        if let Err(e) = check_invariants(&simulation.lanes, simulation.time) {
+            error = Some(e);
+            break;
+        }
+    }
+
+    let error = error.expect("both straight roads were let in the intersection");
+    assert!(error.contains("conflicting routes"), "{}", error);
+    let frame = snapshot(&error);
+    assert_eq!(frame.vehicles.iter().filter(|v| v.stage == Stage::Crossing).count(), 2);
+}
+
+#[test]
+fn a_vehicle_on_its_leader_is_reported() {
+    let mut simulation = simulation();
+    let route = common::route(&mut simulation.lanes, Cross::Third, Itineraire::Left);
+    route.add_vehicle_of_class(Cross::Third.direction(), VehicleClass::Car, &mut simulation.rng);
+    let mut follower = route.vehicles[0].clone();
+    follower.id += 1;
+    route.vehicles.push(follower);
+
+    let error = check_invariants(&simulation.lanes, simulation.time).unwrap_err();
+    assert!(error.contains("runs into"), "{}", error);
+    assert_eq!(snapshot(&error).vehicles.len(), 2);
+}
+
+#[test]
+fn a_left_turn_on_the_u_turn_ahead_of_it_is_reported() {
+    let mut simulation = simulation();
+    let u_turn = common::route(&mut simulation.lanes, Cross::Second, Itineraire::UTurn);
+    u_turn.add_vehicle_of_class(Cross::Second.direction(), VehicleClass::Car, &mut simulation.rng);
+    let mut follower = u_turn.vehicles[0].clone();
+    let left = common::route(&mut simulation.lanes, Cross::Second, Itineraire::Left);
+    follower.id += 1;
+    follower.itineraire = Itineraire::Left;
+    // a few pixels behind, well within the length of the U-turner.
+    let travel = follower.travel_direction();
+    follower.position -= travel * 10;
+    left.vehicles.push(follower);
+
+    let error = check_invariants(&simulation.lanes, simulation.time).unwrap_err();
+    assert!(error.contains("runs into"), "{}", error);
+}
+
+#[test]
+fn a_vehicle_going_backwards_is_reported() {
+    let mut simulation = simulation();
+    let route = common::route(&mut simulation.lanes, Cross::First, Itineraire::Straight);
+    route.add_vehicle_of_class(Cross::First.direction(), VehicleClass::Car, &mut simulation.rng);
+    route.vehicles[0].velocity = -1.0;
+
+    let error = check_invariants(&simulation.lanes, simulation.time).unwrap_err();
+    assert!(error.contains("backwards"), "{}", error);
+}
+
+#[test]
+fn a_vehicle_over_its_speed_limit_is_reported() {
+    let mut simulation = simulation();
+    let route = common::route(&mut simulation.lanes, Cross::Fourth, Itineraire::Straight);
+    route.add_vehicle_of_class(Cross::Fourth.direction(), VehicleClass::Car, &mut simulation.rng);
+    route.vehicles[0].velocity = 50.0;
+
+    let error = check_invariants(&simulation.lanes, simulation.time).unwrap_err();
+    assert!(error.contains("speed limit"), "{}", error);
+}
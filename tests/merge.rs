@@ -0,0 +1,33 @@
+use smart_road::*;
+
+mod common;
+
+#[test]
+fn a_right_turn_waits_for_a_gap_in_the_lane_it_joins() {
+    let mut simulation = common::simulation(common::settings(), Controller::SmartIntersection, 0);
+    simulation.check_invariants = false;
+    let approach = Approach::from(Cross::First);
+    let merge = approach.turn_point(Itineraire::Right, &simulation.settings).unwrap();
+
+    // a car stopped on the merge point, on the straight route of the lane joined.
+    let joined = Cross::from(approach.exit(Itineraire::Right));
+    common::route(&mut simulation.lanes, joined, Itineraire::Straight).add_vehicle_of_class(joined.direction(), VehicleClass::Car, &mut simulation.rng);
+    common::route(&mut simulation.lanes, Cross::First, Itineraire::Right).add_vehicle_of_class(Cross::First.direction(), VehicleClass::Car, &mut simulation.rng);
+
+    for _ in 0..60 * 20 {
+        let blocker = &mut common::route(&mut simulation.lanes, joined, Itineraire::Straight).vehicles[0];
+        blocker.position = merge;
+        blocker.velocity = 0.0;
+        simulation.tick();
+        let right = common::route(&mut simulation.lanes, Cross::First, Itineraire::Right);
+        assert_ne!(right.stage, Stage::Crossing, "the right turn went with the merge area taken");
+        assert_eq!(right.vehicles[0].stage, Stage::Waiting);
+    }
+
+    // the lane clear, it merges.
+    common::route(&mut simulation.lanes, joined, Itineraire::Straight).vehicles.clear();
+    for _ in 0..60 * 20 {
+        simulation.tick();
+    }
+    assert_eq!(simulation.statistic.max_vehicles_passed, 1);
+}
@@ -0,0 +1,58 @@
+use smart_road::*;
+
+mod common;
+
+const SECONDS: usize = 90;
+
+/// Saturates every approach for `SECONDS` with platoons of at most `size`
+/// vehicles `gap` pixels apart, and returns the largest platoon seen and the
+/// vehicles through the intersection.
+fn platoons(size: usize, gap: f64) -> (usize, usize) {
+    let mut settings = common::settings();
+    settings.platoon_size = size;
+    settings.platoon_gap = gap;
+    let mut sim = common::simulation(settings, Controller::SmartIntersection, 0);
+
+    let mut largest = 0;
+    let arrive = |tick: usize, sim: &mut Simulation| {
+        if tick.is_multiple_of(20) {
+            for lane in sim.lanes.iter_mut() {
+                lane.add_vehicle(Itineraire::Straight, Some(VehicleClass::Car), &mut sim.rng);
+                lane.add_vehicle(Itineraire::Left, Some(VehicleClass::Car), &mut sim.rng);
+            }
+        }
+    };
+    common::drive(&mut sim, SECONDS, arrive, |sim| {
+        for route in sim.lanes.iter().flat_map(|lane| lane.routes.iter()) {
+            largest = largest.max(route.platoon.len());
+            // a platoon is taken from the head of the queue.
+            let waiting: Vec<usize> = route.vehicles.iter().filter(|v| v.stage == Stage::Waiting).map(|v| v.id).collect();
+            let members: Vec<usize> = waiting.iter().copied().filter(|id| route.platoon.contains(id)).collect();
+            assert_eq!(members, waiting[..members.len()]);
+        }
+    });
+    (largest, sim.statistic.max_vehicles_passed)
+}
+
+#[test]
+fn platoons_never_outgrow_their_size() {
+    let (largest, _) = platoons(2, 150.0);
+    assert_eq!(largest, 2);
+}
+
+#[test]
+fn platoons_let_more_vehicles_through_than_one_at_a_time() {
+    let (largest, alone) = platoons(1, 0.0);
+    assert_eq!(largest, 1);
+    let (_, grouped) = platoons(4, 150.0);
+    assert!(grouped > alone, "{} vehicles in platoons, {} one at a time", grouped, alone);
+}
+
+#[test]
+fn a_platoon_has_at_least_one_vehicle() {
+    let args = |size: &str| ["--platoon".to_string(), size.to_string()];
+    let mut settings = common::settings();
+    assert!(settings.apply_args(&args("0")).is_err());
+    assert!(settings.apply_args(&args("3")).is_ok());
+    assert_eq!(settings.platoon_size, 3);
+}
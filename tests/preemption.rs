@@ -0,0 +1,29 @@
+use smart_road::*;
+
+mod common;
+
+/// Three cars going straight from the North, a second apart, and an emergency
+/// vehicle going straight from the West `delay` ticks after the first, and
+/// returns the delay the pre-emption caused.
+fn preemption_delay(delay: usize) -> f64 {
+    let mut simulation = common::simulation(common::settings(), Controller::SmartIntersection, 0);
+    let arrive = |tick: usize, simulation: &mut Simulation| {
+        if tick.is_multiple_of(60) && tick < 180 {
+            simulation.lanes[0].add_vehicle(Itineraire::Straight, Some(VehicleClass::Car), &mut simulation.rng);
+        }
+        if tick == delay {
+            simulation.lanes[1].add_vehicle(Itineraire::Straight, Some(VehicleClass::Emergency), &mut simulation.rng);
+        }
+    };
+    common::drive(&mut simulation, 30, arrive, |_| {});
+    assert!(common::routes(&mut simulation.lanes).iter().all(|route| route.vehicles.is_empty()));
+    simulation.statistic.preemption_delay
+}
+
+#[test]
+fn only_the_routes_stopped_for_an_emergency_vehicle_count_its_delay() {
+    // the emergency vehicle there first holds the cars before they are given the intersection.
+    assert_eq!(preemption_delay(0), 0.0);
+    // there after them, it stops the cars given the intersection already.
+    assert!(preemption_delay(1) > 0.0);
+}
@@ -0,0 +1,34 @@
+use rand::{Rng, SeedableRng, StdRng};
+use smart_road::*;
+
+#[test]
+fn the_phase_has_the_most_pressure_of_the_compatible_sets() {
+    let lanes: Vec<(Cross, Itineraire)> = BLOCKS.iter().map(|block| block.lane).collect();
+    let mut rng: StdRng = SeedableRng::from_seed(&[5][..]);
+    for _ in 0..100 {
+        let mut pressures: Vec<((Cross, Itineraire), f64)> = Vec::new();
+        for lane in lanes.iter() {
+            if rng.gen::<f64>() < 0.7 {
+                pressures.push((*lane, rng.gen_range(-3, 8) as f64));
+            }
+        }
+        let phase = max_pressure_phase(&pressures);
+        assert!(phase.iter().all(|a| phase.iter().all(|b| !conflicts(*a, *b))), "{:?}", phase);
+        let sum = |set: &[(Cross, Itineraire)]| -> f64 { pressures.iter().filter(|(l, _)| set.contains(l)).map(|(_, p)| p).sum() };
+
+        let mut best = 0.0_f64;
+        for mask in 0..1u32 << pressures.len() {
+            let set: Vec<(Cross, Itineraire)> = (0..pressures.len()).filter(|i| mask & 1 << i != 0).map(|i| pressures[i].0).collect();
+            if set.iter().all(|a| set.iter().all(|b| !conflicts(*a, *b))) {
+                best = best.max(sum(&set));
+            }
+        }
+        assert_eq!(sum(&phase), best, "{:?}", pressures);
+    }
+}
+
+#[test]
+fn no_route_is_worth_a_grant_without_pressure() {
+    let pressures = [((Cross::First, Itineraire::Left), 0.0), ((Cross::Second, Itineraire::Straight), -2.0)];
+    assert!(max_pressure_phase(&pressures).is_empty());
+}
@@ -0,0 +1,282 @@
+use rand::{Rng, SeedableRng, StdRng};
+use smart_road::*;
+
+mod common;
+
+const CASES: usize = 200;
+/// Random arrival schedules simulated by `random_schedules_are_safe_and_fair`,
+/// `FUZZ_CASES` in the environment runs more.
+const SCHEDULES: usize = 4;
+
+const CROSSES: [Cross; 4] = [Cross::First, Cross::Second, Cross::Third, Cross::Fourth];
+const ITINERAIRES: [Itineraire; 4] = [Itineraire::Left, Itineraire::Straight, Itineraire::Right, Itineraire::UTurn];
+
+/// Something showing up at the intersection on a given tick.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Arrival {
+    Vehicle { tick: usize, cross: Cross, itineraire: Itineraire, class: VehicleClass },
+    Pedestrian { tick: usize, crosswalk: usize },
+}
+
+impl Arrival {
+    fn tick(&self) -> usize {
+        match self {
+            Arrival::Vehicle { tick, .. } | Arrival::Pedestrian { tick, .. } => *tick,
+        }
+    }
+
+    fn apply(&self, simulation: &mut Simulation) {
+        match *self {
+            Arrival::Vehicle { cross, itineraire, class, .. } => simulation.add_vehicle(cross, itineraire, Some(class)),
+            Arrival::Pedestrian { crosswalk, .. } => {
+                let time = simulation.time;
+                simulation.crosswalks[crosswalk].add_pedestrian(time, &mut simulation.rng);
+            }
+        }
+    }
+}
+
+/// Up to a minute of arrivals, from a trickle to more than the intersection takes,
+/// with a few emergency vehicles on a third of the schedules.
+fn random_schedule(rng: &mut StdRng) -> Vec<Arrival> {
+    let count = rng.gen_range(10, 200);
+    let emergencies = rng.gen_range(0, 3) == 0;
+    let classes: Vec<VehicleClass> = CLASSES.iter().map(|spec| spec.class).filter(|c| *c != VehicleClass::Emergency).collect();
+    let mut schedule: Vec<Arrival> = (0..count)
+        .map(|_| {
+            let tick = rng.gen_range(0, 60 * 60);
+            if rng.gen_range(0, 10) == 0 {
+                Arrival::Pedestrian { tick, crosswalk: rng.gen_range(0, 4) }
+            } else {
+                Arrival::Vehicle {
+                    tick,
+                    cross: CROSSES[rng.gen_range(0, 4)],
+                    itineraire: ITINERAIRES[rng.gen_range(0, 4)],
+                    class: if emergencies && rng.gen_range(0, 20) == 0 {
+                        VehicleClass::Emergency
+                    } else {
+                        classes[rng.gen_range(0, classes.len())]
+                    },
+                }
+            }
+        })
+        .collect();
+    schedule.sort_by_key(|a| a.tick());
+    schedule
+}
+
+/// Simulates `schedule` under `controller` with the simulation seeded by `seed`,
+/// until the intersection is empty again, and says why it is not safe or a route
+/// waited too long. The emergency vehicles go before the routes that waited too
+/// long, the wait is only bounded without them.
+fn run(controller: Controller, schedule: &[Arrival], seed: usize) -> Result<(), String> {
+    let emergencies = schedule.iter().any(|a| matches!(a, Arrival::Vehicle { class: VehicleClass::Emergency, .. }));
+    let mut simulation = common::simulation(common::settings(), controller, seed);
+    simulation.check_invariants = false;
+    let bound = if emergencies { f64::INFINITY } else { simulation.wait_bound() };
+
+    let last = schedule.iter().map(|a| a.tick()).max().unwrap_or(0);
+    let mut next = 0;
+    for tick in 0..last + 60 * 180 {
+        while next < schedule.len() && schedule[next].tick() == tick {
+            schedule[next].apply(&mut simulation);
+            next += 1;
+        }
+        simulation.tick();
+
+        check_invariants(&simulation.lanes, simulation.time)?;
+        if simulation.statistic.collisions > 0 {
+            return Err(format!("collision at {:.3}s", simulation.time));
+        }
+        let time = simulation.time;
+        if let Some(route) = common::routes(&mut simulation.lanes).into_iter().find(|r| r.waited(time) > bound) {
+            return Err(format!("{:?} {:?} waited more than {}s at {:.3}s", route.cross, route.itineraire, bound, time));
+        }
+        if tick > last && common::routes(&mut simulation.lanes).iter().all(|r| r.vehicles.is_empty()) {
+            return Ok(());
+        }
+    }
+    Err("vehicles are still in the intersection 3 minutes after the last arrival".to_string())
+}
+
+/// The smallest part of a failing `schedule` that still fails: runs of arrivals
+/// are dropped while the rest keeps failing, from halves down to single arrivals,
+/// then the schedule is moved to start on the first tick. `fails` has to give the
+/// same answer for the same schedule, as `run` does for a given seed.
+fn shrink(mut schedule: Vec<Arrival>, fails: impl Fn(&[Arrival]) -> bool) -> Vec<Arrival> {
+    let mut chunk = schedule.len() / 2;
+    while chunk > 0 {
+        let mut start = 0;
+        let mut dropped = false;
+        while start < schedule.len() {
+            let end = (start + chunk).min(schedule.len());
+            let candidate: Vec<Arrival> = schedule[..start].iter().chain(schedule[end..].iter()).cloned().collect();
+            if !candidate.is_empty() && fails(&candidate) {
+                schedule = candidate;
+                dropped = true;
+            } else {
+                start += chunk;
+            }
+        }
+        if !dropped {
+            chunk /= 2;
+        }
+    }
+
+    let first = schedule.iter().map(|a| a.tick()).min().unwrap_or(0);
+    let moved: Vec<Arrival> = schedule
+        .iter()
+        .map(|a| match *a {
+            Arrival::Vehicle { tick, cross, itineraire, class } => Arrival::Vehicle { tick: tick - first, cross, itineraire, class },
+            Arrival::Pedestrian { tick, crosswalk } => Arrival::Pedestrian { tick: tick - first, crosswalk },
+        })
+        .collect();
+    if first > 0 && fails(&moved) {
+        return moved;
+    }
+    schedule
+}
+
+#[test]
+fn random_schedules_are_safe_and_fair() {
+    let cases = std::env::var("FUZZ_CASES").ok().and_then(|n| n.parse().ok()).unwrap_or(SCHEDULES);
+    for seed in 0..cases {
+        let mut rng: StdRng = SeedableRng::from_seed(&[seed][..]);
+        let schedule = random_schedule(&mut rng);
+        for controller in Controller::ALL {
+            if let Err(e) = run(controller, &schedule, seed) {
+                let minimal = shrink(schedule.clone(), |s| run(controller, s, seed).is_err());
+                panic!("schedule {} fails under the {}: {}\nshrunk to {} arrivals: {:#?}", seed, controller.name(), e, minimal.len(), minimal);
+            }
+        }
+    }
+}
+
+#[test]
+fn a_schedule_and_a_seed_always_run_the_same_way() {
+    let mut rng: StdRng = SeedableRng::from_seed(&[13][..]);
+    let schedule = random_schedule(&mut rng);
+    // the pedestrians arriving on their own are drawn by the simulation as well.
+    let settings = Rc::new(common::default_settings());
+    let trace = |seed: usize| {
+        let mut simulation = Simulation::with_seed(settings.clone(), Controller::SmartIntersection, seed);
+        simulation.check_invariants = false;
+        let mut trace = Vec::new();
+        for tick in 0..60 * 40 {
+            for arrival in schedule.iter().filter(|a| a.tick() == tick) {
+                arrival.apply(&mut simulation);
+            }
+            simulation.tick();
+            let vehicles = simulation.lanes.iter().flat_map(|lane| lane.routes.iter()).flat_map(|route| route.vehicles.iter());
+            trace.extend(vehicles.map(|v| (tick, v.position.x, v.position.y, v.velocity.to_bits())));
+            // the sprites too, a replay shows them.
+            let frame = Frame::capture(&simulation.lanes, simulation.time);
+            trace.extend(frame.vehicles.iter().map(|v| (tick, v.x, v.y, v.texture as u32)));
+        }
+        (trace, simulation.statistic.pedestrians_crossed, simulation.statistic.max_vehicles_passed)
+    };
+
+    assert_eq!(trace(3), trace(3));
+}
+
+#[test]
+fn shrinking_keeps_only_the_arrivals_that_make_it_fail() {
+    let mut rng: StdRng = SeedableRng::from_seed(&[7][..]);
+    let schedule = random_schedule(&mut rng);
+    let (a, b) = (schedule[schedule.len() / 3], schedule[schedule.len() * 2 / 3]);
+    let same = |x: &Arrival, y: &Arrival| {
+        matches!((x, y), (Arrival::Vehicle { cross: c, itineraire: i, .. }, Arrival::Vehicle { cross: d, itineraire: j, .. }) if c == d && i == j)
+            || matches!((x, y), (Arrival::Pedestrian { crosswalk: c, .. }, Arrival::Pedestrian { crosswalk: d, .. }) if c == d)
+    };
+
+    let minimal = shrink(schedule.clone(), |s| s.iter().any(|x| same(x, &a)) && s.iter().any(|x| same(x, &b)));
+    assert!(minimal.len() <= 2, "{:?}", minimal);
+    assert!(minimal.iter().any(|x| same(x, &a)) && minimal.iter().any(|x| same(x, &b)));
+    assert_eq!(minimal.iter().map(|x| x.tick()).min(), Some(0));
+}
+
+#[test]
+fn distance_to_stop_point_is_the_nearest_vehicle_before_the_line() {
+    let mut rng: StdRng = SeedableRng::from_seed(&[42][..]);
+    for _ in 0..CASES {
+        let settings = Rc::new(common::random_settings(&mut rng));
+        let mut simulation = Simulation::new(settings.clone(), Controller::SmartIntersection);
+        let route = common::routes(&mut simulation.lanes).into_iter().nth(rng.gen_range(0, 16)).unwrap();
+        assert_eq!(route.distance_to_stop_point(), 10000, "an empty route has nothing to stop");
+
+        // a queue from the head: each vehicle further back along the way in.
+        let approach = Approach::from(route.cross);
+        let (dx, dy) = approach.spec().travel;
+        route.add_vehicle(route.cross.direction(), &mut rng);
+        let template = route.vehicles[0].clone();
+        route.vehicles.clear();
+        let stop = route.stop_point.x * dx + route.stop_point.y * dy;
+        let mut along = stop + rng.gen_range(-100, 200);
+        let mut expected = None;
+        for i in 0..rng.gen_range(1, 6) {
+            let mut vehicle = template.clone();
+            vehicle.id += i;
+            if dx != 0 {
+                vehicle.position.x = along * dx;
+            } else {
+                vehicle.position.y = along * dy;
+            }
+            // the U-turns heading back don't count.
+            vehicle.is_changed_direction = rng.gen_range(0, 4) == 0;
+
+            if expected.is_none() && !vehicle.is_changed_direction && along < stop {
+                expected = Some((stop - along) as usize);
+            }
+            route.vehicles.push(vehicle);
+            along -= rng.gen_range(settings.vehicle, 150);
+        }
+
+        assert_eq!(route.distance_to_stop_point(), expected.unwrap_or(10000));
+    }
+}
+
+#[test]
+fn chunk_routes_picks_the_routes_of_a_block_in_order() {
+    let mut rng: StdRng = SeedableRng::from_seed(&[3][..]);
+    let mut simulation = common::simulation(common::default_settings(), Controller::SmartIntersection, 0);
+    for _ in 0..CASES {
+        let block: Vec<(Cross, Itineraire)> = (0..rng.gen_range(0, 8))
+            .map(|_| (CROSSES[rng.gen_range(0, 4)], ITINERAIRES[rng.gen_range(0, 4)]))
+            .collect();
+        let mut all = common::routes(&mut simulation.lanes);
+        rng.shuffle(&mut all);
+        let order: Vec<(Cross, Itineraire)> = all.iter().map(|r| (r.cross, r.itineraire)).collect();
+
+        let chunk: Vec<(Cross, Itineraire)> = chunk_routes(all, &block).iter().map(|r| (r.cross, r.itineraire)).collect();
+        let expected: Vec<(Cross, Itineraire)> = order.into_iter().filter(|key| block.contains(key)).collect();
+        assert_eq!(chunk, expected);
+    }
+}
+
+#[test]
+fn blocks_are_the_routes_whose_paths_cross() {
+    for block in BLOCKS.iter() {
+        assert!(block.intersections.contains(&block.lane), "{:?} is not in its own block", block.lane);
+        assert_eq!(BLOCKS.iter().filter(|b| b.lane == block.lane).count(), 1, "{:?} has two blocks", block.lane);
+        for other in block.intersections.iter() {
+            let back = BLOCKS.iter().find(|b| b.lane == *other).map(|b| b.intersections.contains(&block.lane));
+            assert_eq!(back, Some(true), "{:?} blocks {:?} but not the other way", block.lane, other);
+        }
+    }
+
+    let mut rng: StdRng = SeedableRng::from_seed(&[11][..]);
+    for _ in 0..20 {
+        let simulation = Simulation::new(Rc::new(common::random_settings(&mut rng)), Controller::SmartIntersection);
+        let all: Vec<&Route> = simulation.lanes.iter().flat_map(|lane| lane.routes.iter()).collect();
+        for a in all.iter() {
+            for b in all.iter().filter(|b| b.cross != a.cross) {
+                let blocked = conflicts((a.cross, a.itineraire), (b.cross, b.itineraire));
+                assert_eq!(
+                    blocked,
+                    !conflict_points(a, b).is_empty(),
+                    "{:?} {:?} and {:?} {:?}", a.cross, a.itineraire, b.cross, b.itineraire
+                );
+            }
+        }
+    }
+}
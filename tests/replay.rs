@@ -0,0 +1,84 @@
+use smart_road::*;
+
+mod common;
+
+/// A simulation with vehicles of every class on every approach, a few seconds in.
+fn busy_simulation() -> Simulation {
+    let mut settings = common::default_settings();
+    settings.mix.push((VehicleClass::Emergency, 5));
+    let mut simulation = common::simulation(settings, Controller::SmartIntersection, 4);
+    for tick in 0..60 * 6 {
+        if tick % 30 == 0 {
+            for lane in simulation.lanes.iter_mut() {
+                lane.add_vehicle(Itineraire::Left, None, &mut simulation.rng);
+            }
+        }
+        simulation.tick();
+    }
+    simulation
+}
+
+#[test]
+fn a_frame_reads_back_as_it_was_written() {
+    let simulation = busy_simulation();
+    let frame = Frame::capture(&simulation.lanes, simulation.time);
+    assert!(frame.vehicles.len() > 4);
+    assert!(frame.routes.iter().any(|r| r.stage == Stage::Crossing));
+
+    let line = frame.to_json().to_string();
+    let json = Json::parse(&line).unwrap();
+    assert_eq!(Frame::from_json(&json).unwrap(), frame);
+}
+
+#[test]
+fn the_parser_reads_json_back_and_rejects_malformed_input() {
+    let parsed = Json::parse(r#" {"a": [1, -2.5e3, true, null], "b": "q\"\\\/\né"} "#).unwrap();
+    assert_eq!(parsed.field("a").unwrap().as_array().unwrap()[1].as_f64(), Some(-2500.0));
+    assert_eq!(parsed.field("b").unwrap().as_str(), Some("q\"\\/\né"));
+    assert_eq!(Json::parse(&parsed.to_string()).unwrap(), parsed);
+
+    for bad in [
+        r#""\x""#,
+        r#""\u00""#,
+        r#""\"#,
+        r#"{"a": 1"#,
+        r#"[1, 2"#,
+        r#""open"#,
+        "",
+        "tru",
+        r#"{"a": 1} x"#,
+        "[1] [2]",
+        "1.2.3",
+    ] {
+        assert!(Json::parse(bad).is_err(), "{} parsed", bad);
+    }
+}
+
+#[test]
+fn the_playback_stays_within_the_log() {
+    let path = std::env::temp_dir().join(format!("smart-road-replay-{}.jsonl", std::process::id()));
+    let path = path.to_str().unwrap();
+    let mut simulation = common::simulation(common::settings(), Controller::SmartIntersection, 0);
+    let mut recorder = Recorder::create(path).unwrap();
+    for _ in 0..10 {
+        simulation.tick();
+        recorder.record(&simulation.lanes, simulation.time).unwrap();
+    }
+    recorder.flush().unwrap();
+    let mut replay = Replay::load(path).unwrap();
+    std::fs::remove_file(path).unwrap();
+
+    let (first, last) = (replay.frames[0].time, replay.duration());
+    assert_eq!(replay.frames.len(), 10);
+    replay.seek(-5.0);
+    assert_eq!((replay.time, replay.frame().time), (first, first));
+    replay.seek(5.0);
+    assert_eq!((replay.time, replay.frame().time), (last, last));
+
+    replay.step(-3);
+    assert_eq!(replay.frame().time, replay.frames[6].time);
+    replay.step(100);
+    assert_eq!(replay.frame().time, last);
+    replay.step(-100);
+    assert_eq!((replay.time, replay.frame().time), (first, first));
+}
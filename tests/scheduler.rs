@@ -0,0 +1,99 @@
+use rand::{Rng, SeedableRng, StdRng};
+use smart_road::*;
+
+const CASES: usize = 100;
+
+fn delay(jobs: &[Job], starts: &[f64]) -> f64 {
+    jobs.iter().zip(starts).map(|(job, start)| job.weight * (start - job.release)).sum()
+}
+
+/// The least delay over every order of the jobs, each one starting as soon as
+/// the ones before it whose paths cross its own are out.
+fn brute_force(jobs: &[Job], order: &mut Vec<usize>) -> f64 {
+    if order.len() == jobs.len() {
+        let mut starts = vec![0.0; jobs.len()];
+        for (i, j) in order.iter().enumerate() {
+            starts[*j] = order[..i]
+                .iter()
+                .filter(|k| conflicts(jobs[**k].lane, jobs[*j].lane))
+                .map(|k| starts[*k] + jobs[*k].duration)
+                .fold(jobs[*j].release, f64::max);
+        }
+        return delay(jobs, &starts);
+    }
+
+    let mut best = f64::INFINITY;
+    for j in 0..jobs.len() {
+        if !order.contains(&j) {
+            order.push(j);
+            best = best.min(brute_force(jobs, order));
+            order.pop();
+        }
+    }
+    best
+}
+
+#[test]
+fn the_schedule_is_the_best_order_of_the_jobs() {
+    let mut rng: StdRng = SeedableRng::from_seed(&[3][..]);
+    let mut lanes: Vec<(Cross, Itineraire)> = BLOCKS.iter().map(|block| block.lane).collect();
+    for _ in 0..CASES {
+        rng.shuffle(&mut lanes);
+        let jobs: Vec<Job> = lanes[..rng.gen_range(1, 7)]
+            .iter()
+            .map(|lane| Job {
+                lane: *lane,
+                release: rng.gen_range(0.0, 5.0),
+                duration: rng.gen_range(0.5, 4.0),
+                weight: rng.gen_range(1, 5) as f64,
+            })
+            .collect();
+
+        let (starts, complete) = schedule(&jobs, &[]);
+        assert!(complete);
+        for (j, job) in jobs.iter().enumerate() {
+            assert!(starts[j] >= job.release);
+            for (k, other) in jobs.iter().enumerate().filter(|(_, other)| conflicts(job.lane, other.lane)) {
+                assert!(starts[j] >= starts[k] + other.duration || starts[k] >= starts[j] + job.duration, "{:?} and {:?} overlap", job, other);
+            }
+        }
+        assert!((delay(&jobs, &starts) - brute_force(&jobs, &mut Vec::new())).abs() < 1e-9, "{:?}", jobs);
+    }
+}
+
+#[test]
+fn the_routes_in_the_intersection_hold_back_the_ones_crossing_them() {
+    let straight = (Cross::First, Itineraire::Straight);
+    let crossing = BLOCKS.iter().find(|block| block.lane == straight).unwrap().intersections.iter().find(|lane| **lane != straight).unwrap();
+    let apart = BLOCKS.iter().map(|block| block.lane).find(|lane| *lane != straight && !conflicts(*lane, straight)).unwrap();
+
+    let jobs = [
+        Job { lane: *crossing, release: 1.0, duration: 2.0, weight: 1.0 },
+        Job { lane: apart, release: 1.0, duration: 2.0, weight: 1.0 },
+    ];
+    assert_eq!(schedule(&jobs, &[(straight, 3.0)]), (vec![3.0, 1.0], true));
+}
+
+#[test]
+fn a_search_cut_at_its_node_limit_says_so() {
+    // every route at once: the bound prunes them within `MAX_NODES`, not within 50.
+    let jobs: Vec<Job> = BLOCKS
+        .iter()
+        .enumerate()
+        .map(|(i, block)| Job { lane: block.lane, release: i as f64 * 0.1, duration: 2.0 + (i % 3) as f64, weight: 1.0 + (i % 4) as f64 })
+        .collect();
+    let (best, complete) = schedule(&jobs, &[]);
+    assert!(complete, "{} jobs in more than {} nodes", jobs.len(), MAX_NODES);
+    let (starts, complete) = schedule_with_limit(&jobs, &[], 50);
+    assert!(!complete);
+    assert!(delay(&jobs, &starts) >= delay(&jobs, &best) - 1e-9);
+
+    // still a schedule the routes can follow.
+    assert_eq!(starts.len(), jobs.len());
+    for (j, job) in jobs.iter().enumerate() {
+        assert!(starts[j] >= job.release);
+        for (k, other) in jobs.iter().enumerate().filter(|(_, other)| conflicts(job.lane, other.lane)) {
+            assert!(starts[j] >= starts[k] + other.duration || starts[k] >= starts[j] + job.duration, "{:?} and {:?} overlap", job, other);
+        }
+    }
+}
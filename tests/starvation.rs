@@ -0,0 +1,49 @@
+use smart_road::*;
+
+mod common;
+
+const MAX_WAIT: f64 = 20.0;
+const SECONDS: usize = 150;
+
+const ITINERAIRES: [Itineraire; 4] = [Itineraire::Left, Itineraire::Straight, Itineraire::Right, Itineraire::UTurn];
+
+/// Every route with a new vehicle each tick on `heavy`, one every `light_every`
+/// seconds on the others, and returns the longest wait seen on any route and
+/// the bound it must stay within.
+fn longest_wait(heavy: impl Fn(Cross, Itineraire) -> bool, light_every: usize) -> (f64, f64) {
+    let mut settings = common::settings();
+    settings.max_wait = MAX_WAIT;
+    let mut sim = common::simulation(settings, Controller::SmartIntersection, 0);
+
+    let mut longest: f64 = 0.0;
+    let arrive = |tick: usize, sim: &mut Simulation| {
+        for i in 0..sim.lanes.len() {
+            let cross = sim.lanes[i].cross;
+            for itineraire in ITINERAIRES {
+                if heavy(cross, itineraire) || tick.is_multiple_of(light_every * 60) {
+                    sim.lanes[i].add_vehicle(itineraire, None, &mut sim.rng);
+                }
+            }
+        }
+    };
+    common::drive(&mut sim, SECONDS, arrive, |sim| {
+        for route in sim.lanes.iter().flat_map(|lane| lane.routes.iter()) {
+            longest = longest.max(route.waited(sim.time));
+        }
+    });
+    (longest, sim.wait_bound())
+}
+
+#[test]
+fn a_light_route_crossing_saturated_ones_still_gets_through() {
+    // the straight roads keep the intersection busy, so the left turns and
+    // U-turns conflicting with them come rarely and never first.
+    let (longest, bound) = longest_wait(|_, itineraire| itineraire == Itineraire::Straight, 10);
+    assert!(longest <= bound, "a route waited {:.1}s, more than {:.1}s", longest, bound);
+}
+
+#[test]
+fn no_route_waits_past_the_bound_when_all_are_saturated() {
+    let (longest, bound) = longest_wait(|_, _| true, 1);
+    assert!(longest <= bound, "a route waited {:.1}s, more than {:.1}s", longest, bound);
+}
@@ -0,0 +1,45 @@
+use rand::{Rng, SeedableRng, StdRng};
+use smart_road::*;
+
+mod common;
+
+/// The `q` quantile of the sorted `samples`, the nearest rank.
+fn exact(samples: &[f64], q: f64) -> f64 {
+    samples[((q * samples.len() as f64).ceil() as usize).clamp(1, samples.len()) - 1]
+}
+
+#[test]
+fn the_percentiles_are_close_to_the_exact_ones() {
+    let mut rng: StdRng = SeedableRng::from_seed(&[17][..]);
+    // skewed like the times to pass: most short, a long tail.
+    let mut samples: Vec<f64> = (0..20_000).map(|_| -10.0 * (1.0 - rng.gen::<f64>()).ln()).collect();
+    let mut aggregate = Aggregate::new();
+    for sample in samples.iter() {
+        aggregate.push(*sample);
+    }
+    samples.sort_by(f64::total_cmp);
+
+    assert_eq!(aggregate.count, samples.len());
+    assert_eq!((aggregate.min, aggregate.max), (samples[0], samples[samples.len() - 1]));
+    // P² is an estimate, a few percent off in the tail.
+    for (estimate, q) in [(aggregate.p50(), 0.5), (aggregate.p90(), 0.9), (aggregate.p99(), 0.99)] {
+        let exact = exact(&samples, q);
+        assert!((estimate - exact).abs() < 0.05 * exact, "p{} is {} for {}", q * 100.0, estimate, exact);
+    }
+}
+
+#[test]
+fn the_slowest_speed_is_never_above_the_fastest() {
+    let mut simulation = common::simulation(common::settings(), Controller::SmartIntersection, 2);
+    for tick in 0..60 * 40 {
+        if tick % 45 == 0 {
+            let lane = tick / 45 % simulation.lanes.len();
+            simulation.lanes[lane].add_vehicle(Itineraire::Straight, None, &mut simulation.rng);
+        }
+        simulation.tick();
+    }
+    let statistic = &simulation.statistic;
+    assert!(statistic.max_vehicles_passed > 1);
+    assert!(statistic.min_velocity <= statistic.max_velocity, "{} above {}", statistic.min_velocity, statistic.max_velocity);
+    assert!(statistic.min_velocity >= 0.0);
+}